@@ -0,0 +1,33 @@
+use criterion::{
+	Criterion,
+	black_box, criterion_group, criterion_main,
+};
+use slicepat::{
+	ByteMatch, ExactMatch, ExactPathMatch, Matcher, PathMatch,
+};
+
+/// Paths like those of a game server's content, with the needle only in the last one.
+fn haystack() -> Vec<u8> {
+	let mut haystack = Vec::new();
+	for i in 0..10_000 {
+		haystack.extend_from_slice(format!("materials/models/props_{i}/metal_{i}.vtf\n").as_bytes());
+	}
+	haystack.extend_from_slice(b"sound/custom/intro.wav\n");
+	haystack
+}
+
+fn find(c: &mut Criterion) {
+	fn bench<M: Matcher<u8>>(c: &mut Criterion, name: &str, matcher: M, needle: &[u8], haystack: &[u8]) {
+		c.bench_function(name, move |b| b.iter(|| matcher.find(black_box(needle), black_box(haystack))));
+	}
+	let haystack = haystack();
+	let needle = b"intro.wav";
+	bench(c, "ExactMatch", ExactMatch, needle, &haystack);
+	bench(c, "ByteMatch", ByteMatch, needle, &haystack);
+	bench(c, "ExactPathMatch", ExactPathMatch, needle, &haystack);
+	bench(c, "ExactPathMatch with separators", ExactPathMatch, b"custom/intro.wav", &haystack);
+	bench(c, "PathMatch", PathMatch, needle, &haystack);
+}
+
+criterion_group!(benches, find);
+criterion_main!(benches);
@@ -0,0 +1,150 @@
+#[cfg(feature = "alloc")]
+use alloc::{
+	boxed::Box,
+	vec::Vec,
+};
+
+use crate::{
+	Matcher, Pattern, Pieces,
+};
+
+/// Test on a whole haystack, such as whether a [`Pattern`] matches it, that can be combined with others.
+pub trait Condition<T> {
+	/// Returns `true` if `haystack` passes the condition, comparing elements with `matcher`.
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool;
+
+	/// Combine with `other` into a condition that both must pass.
+	#[inline]
+	fn and<C: Condition<T>>(self, other: C) -> And<Self, C>
+	where
+		Self: Sized,
+	{
+		And(self, other)
+	}
+
+	/// Combine with `other` into a condition that either may pass.
+	#[inline]
+	fn or<C: Condition<T>>(self, other: C) -> Or<Self, C>
+	where
+		Self: Sized,
+	{
+		Or(self, other)
+	}
+
+	/// Turn into a condition that passes where this one doesn't.
+	#[inline]
+	fn negate(self) -> Not<Self>
+	where
+		Self: Sized,
+	{
+		Not(self)
+	}
+}
+
+impl<T, C: Condition<T>> Condition<T> for &C {
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool {
+		Condition::is_match(*self, matcher, haystack)
+	}
+}
+
+/// Passes if the pattern matches the whole haystack.
+impl<P: Pieces<T>, T: PartialEq> Condition<T> for Pattern<P, T> {
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool {
+		self.first_match(matcher, haystack).is_some()
+	}
+}
+
+/// Condition that both conditions must pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<T, A: Condition<T>, B: Condition<T>> Condition<T> for And<A, B> {
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool {
+		self.0.is_match(matcher, haystack) && self.1.is_match(matcher, haystack)
+	}
+}
+
+/// Condition that either condition may pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<T, A: Condition<T>, B: Condition<T>> Condition<T> for Or<A, B> {
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool {
+		self.0.is_match(matcher, haystack) || self.1.is_match(matcher, haystack)
+	}
+}
+
+/// Condition that passes where the inner condition doesn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Not<C>(pub C);
+
+impl<T, C: Condition<T>> Condition<T> for Not<C> {
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool {
+		!self.0.is_match(matcher, haystack)
+	}
+}
+
+/// Combination of patterns built at runtime, such as from a parsed expression.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr<P, T> {
+	Pattern(Pattern<P, T>),
+	/// Passes if all of the expressions pass, including if there are none.
+	All(Vec<Self>),
+	/// Passes if any of the expressions pass, so not if there are none.
+	Any(Vec<Self>),
+	Not(Box<Self>),
+}
+
+#[cfg(feature = "alloc")]
+impl<P: Pieces<T>, T: PartialEq> Condition<T> for Expr<P, T> {
+	fn is_match<M: Matcher<T>>(&self, matcher: &M, haystack: &[T]) -> bool {
+		match self {
+			Self::Pattern(pattern) => pattern.is_match(matcher, haystack),
+			Self::All(exprs) => exprs.iter().all(move |expr| expr.is_match(matcher, haystack)),
+			Self::Any(exprs) => exprs.iter().any(move |expr| expr.is_match(matcher, haystack)),
+			Self::Not(expr) => !expr.is_match(matcher, haystack),
+		}
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<P, T> From<Pattern<P, T>> for Expr<P, T> {
+	#[inline]
+	fn from(value: Pattern<P, T>) -> Self {
+		Self::Pattern(value)
+	}
+}
+
+#[cfg(feature = "alloc")]
+#[cfg(test)]
+mod tests {
+	use alloc::{
+		boxed::Box,
+		vec,
+	};
+
+	use crate::*;
+	use u8_buf::*;
+
+	#[test]
+	fn conditions_combine() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+		let textures = parse(b"materials/**").and(parse(b"**/*.vtf")).and(parse(b"materials/dev/**").negate());
+		assert!(textures.is_match(&PathMatch, b"materials/brick/wall.vtf"));
+		assert!(!textures.is_match(&PathMatch, b"materials/dev/wall.vtf"));
+		assert!(!textures.is_match(&PathMatch, b"materials/brick/wall.vmt"));
+		assert!(parse(b"**/*.vtf").or(parse(b"**/*.vmt")).is_match(&PathMatch, b"materials/brick/wall.vmt"));
+
+		let expr = Expr::All(vec![
+			parse(b"materials/**").into(),
+			Expr::Any(vec![parse(b"**/*.vtf").into(), parse(b"**/*.vmt").into()]),
+			Expr::Not(Box::new(parse(b"materials/dev/**").into())),
+		]);
+		assert!(expr.is_match(&PathMatch, b"Materials/Brick/Wall.VMT"));
+		assert!(!expr.is_match(&PathMatch, b"materials/dev/wall.vmt"));
+		assert!(!expr.is_match(&PathMatch, b"sound/wall.vmt"));
+		assert!(Expr::<U8Pieces, u8>::All(vec![]).is_match(&PathMatch, b"a"));
+		assert!(!Expr::<U8Pieces, u8>::Any(vec![]).is_match(&PathMatch, b"a"));
+	}
+}
@@ -0,0 +1,273 @@
+use crate::{
+	Alternation, Class, Pattern, PatternFlags, Piece, Syntax,
+};
+
+/// Build a [`Pattern`] from a string literal at compile time, as [`Pattern::parse_with`] would at runtime.
+///
+/// The pattern is parsed with [`Syntax::<u8>::GLOB`], unless a constant [`Syntax<u8>`] is given after it. Its pieces
+/// are stored in a constant, so the pattern is a `Pattern<&'static [Piece<'static, u8>], u8>` that can be used in
+/// constants and statics, without `alloc`.
+///
+/// ```
+/// use slicepat::{pattern, ExactMatch, Pattern, Piece, Syntax};
+///
+/// const MAPS: &[Pattern<&[Piece<u8>], u8>] = &[
+///     pattern!("maps/*.bsp"),
+///     pattern!("maps/**/*.nav", Syntax::<u8>::PATH_GLOB),
+/// ];
+/// assert!(MAPS[0].first_match(ExactMatch, b"maps/cp_well.bsp").is_some());
+/// ```
+#[macro_export]
+macro_rules! pattern {
+	($pattern:expr $(,)?) => {
+		$crate::pattern!($pattern, $crate::Syntax::<u8>::GLOB)
+	};
+	($pattern:expr, $syntax:expr $(,)?) => {{
+		const PATTERN: &[u8] = <str>::as_bytes($pattern);
+		const SYNTAX: $crate::Syntax<u8> = $syntax;
+		const PARSED: $crate::Pattern<[$crate::Piece<'static, u8>; SYNTAX.piece_count(PATTERN)], u8> =
+			$crate::Pattern::parse_const(PATTERN, &SYNTAX);
+		const PIECES: &[$crate::Piece<'static, u8>] = &PARSED.pieces;
+		$crate::Pattern::<&[$crate::Piece<'static, u8>], u8>::new(PIECES, PARSED.flags)
+	}};
+}
+
+impl Syntax<u8> {
+	/// Get the number of pieces that [`Pattern::parse_const`] parses `pattern` into.
+	pub const fn piece_count(&self, pattern: &[u8]) -> usize {
+		let (_, mut tokens) = ConstTokens::new(pattern, self);
+		let mut count = 0;
+		while tokens.next().is_some() {
+			count += 1;
+		}
+		count
+	}
+}
+
+impl<'a, const N: usize> Pattern<[Piece<'a, u8>; N], u8> {
+	/// Parse `pattern` like [`parse_with`](Self::parse_with), in a `const` context.
+	///
+	/// # Panics
+	/// Panics if `N` isn't the [`piece_count`](Syntax::piece_count) of `pattern`.
+	pub const fn parse_const(pattern: &'a [u8], syntax: &Syntax<u8>) -> Self {
+		let (flags, mut tokens) = ConstTokens::new(pattern, syntax);
+		let mut pieces = [Piece::Wildcard; N];
+		let mut count = 0;
+		while let Some(piece) = tokens.next() {
+			assert!(count < N, "more pieces in pattern than in array");
+			pieces[count] = piece;
+			count += 1;
+		}
+		assert!(count == N, "fewer pieces in pattern than in array");
+		Self::new(pieces, flags)
+	}
+}
+
+/// `Tokens`, for `u8` patterns in a `const` context.
+struct ConstTokens<'a, 's> {
+	pattern: &'a [u8],
+	rest: &'a [u8],
+	syntax: &'s Syntax<u8>,
+}
+
+impl<'a, 's> ConstTokens<'a, 's> {
+	/// Get the flags of `pattern` and the tokens of its pieces, as [`Pattern::parse_with`] does.
+	const fn new(pattern: &'a [u8], syntax: &'s Syntax<u8>) -> (PatternFlags, Self) {
+		if syntax.separator.is_some() {
+			let flags = PatternFlags::empty().with_path_aware().with_end_anchored();
+			return (flags, Self { pattern, rest: pattern, syntax })
+		}
+
+		let mut start = 0;
+		while start < pattern.len() && pattern[start] == syntax.wildcard {
+			start += 1;
+		}
+		let mut end = pattern.len();
+		while end > start && pattern[end - 1] == syntax.wildcard {
+			end -= 1;
+		}
+		if end < pattern.len() && ends_with_escape(syntax, pattern.split_at(end).0) {
+			end += 1;
+		}
+
+		let mut flags = PatternFlags::empty();
+		if start > 0 {
+			flags = flags.with_start_unanchored();
+		}
+		if pattern.is_empty() || (start < end && end == pattern.len()) {
+			flags = flags.with_end_anchored();
+		}
+		let rest = pattern.split_at(end).0.split_at(start).1;
+		(flags, Self { pattern, rest, syntax })
+	}
+
+	const fn next(&mut self) -> Option<Piece<'a, u8>> {
+		let Some((&first, rest)) = self.rest.split_first() else {
+			return None
+		};
+		if is(self.syntax.escape, first) && !rest.is_empty() {
+			let literal;
+			(literal, self.rest) = rest.split_at(1);
+			return Some(Piece::Literal(literal))
+		}
+		if first == self.syntax.wildcard {
+			let mut wildcards = 0;
+			while wildcards < rest.len() && rest[wildcards] == self.syntax.wildcard {
+				wildcards += 1;
+			}
+			let before = self.pattern.split_at(self.pattern.len() - self.rest.len()).0;
+			self.rest = rest.split_at(wildcards).1;
+			let separator = match self.syntax.separator {
+				Some(separator) if wildcards > 0 => separator,
+				_ => return Some(Piece::Wildcard),
+			};
+			if let Some(&last) = before.last() {
+				if last != separator {
+					return Some(Piece::Wildcard)
+				}
+			}
+			match self.rest.split_first() {
+				Some((&t, rest)) if t == separator => self.rest = rest,
+				Some(_) => return Some(Piece::Wildcard),
+				None => self.rest = self.pattern.split_at(self.pattern.len() - 1).1,
+			}
+			return Some(Piece::Globstar)
+		}
+		if is(self.syntax.any, first) {
+			self.rest = rest;
+			return Some(Piece::Any)
+		}
+		if let Some((class, rest)) = class_at(self.syntax, self.rest) {
+			self.rest = rest;
+			return Some(Piece::Class(class))
+		}
+		if let Some((alternation, rest)) = alternation_at(self.syntax, self.rest) {
+			self.rest = rest;
+			return Some(Piece::Alternation(alternation))
+		}
+
+		let mut len = 1;
+		while len < self.rest.len() && !is_special(self.syntax, self.rest.split_at(len).1) {
+			len += 1;
+		}
+		let literal;
+		(literal, self.rest) = self.rest.split_at(len);
+		Some(Piece::Literal(literal))
+	}
+}
+
+const fn is(element: Option<u8>, t: u8) -> bool {
+	matches!(element, Some(element) if element == t)
+}
+
+const fn ends_with_escape(syntax: &Syntax<u8>, pattern: &[u8]) -> bool {
+	let mut escapes = 0;
+	while escapes < pattern.len() && is(syntax.escape, pattern[pattern.len() - 1 - escapes]) {
+		escapes += 1;
+	}
+	escapes % 2 == 1
+}
+
+const fn is_special(syntax: &Syntax<u8>, pattern: &[u8]) -> bool {
+	let Some(&first) = pattern.first() else {
+		return false
+	};
+	first == syntax.wildcard
+		|| is(syntax.any, first)
+		|| is(syntax.escape, first)
+		|| class_at(syntax, pattern).is_some()
+		|| alternation_at(syntax, pattern).is_some()
+}
+
+const fn class_at<'a>(syntax: &Syntax<u8>, pattern: &'a [u8]) -> Option<(Class<'a, u8>, &'a [u8])> {
+	let Some(class_syntax) = syntax.class else {
+		return None
+	};
+	let body = match pattern.split_first() {
+		Some((&open, body)) if open == class_syntax.open => body,
+		_ => return None,
+	};
+	let (negated, body) = match body.split_first() {
+		Some((&negate, rest)) if negate == class_syntax.negate => (true, rest),
+		_ => (false, body),
+	};
+	// A closing element right at the start is a member, so that it can be in the class.
+	let mut close = 1;
+	while close < body.len() && body[close] != class_syntax.close {
+		close += 1;
+	}
+	if close >= body.len() {
+		return None
+	}
+	let (set, rest) = body.split_at(close);
+	let mut range = None;
+	let mut i = 0;
+	while i < set.len() {
+		if set[i] == class_syntax.range {
+			range = Some(&set[i]);
+			break
+		}
+		i += 1;
+	}
+	Some((Class { set, range, negated }, rest.split_at(1).1))
+}
+
+const fn alternation_at<'a>(syntax: &Syntax<u8>, pattern: &'a [u8]) -> Option<(Alternation<'a, u8>, &'a [u8])> {
+	let Some(alternation_syntax) = syntax.alternation else {
+		return None
+	};
+	let body = match pattern.split_first() {
+		Some((&open, body)) if open == alternation_syntax.open => body,
+		_ => return None,
+	};
+	let mut close = 0;
+	while close < body.len() && body[close] != alternation_syntax.close {
+		close += 1;
+	}
+	if close >= body.len() {
+		return None
+	}
+	let (alternatives, rest) = body.split_at(close);
+	let mut separator = None;
+	let mut i = 0;
+	while i < alternatives.len() {
+		if alternatives[i] == alternation_syntax.separator {
+			separator = Some(&alternatives[i]);
+			break
+		}
+		i += 1;
+	}
+	Some((Alternation { alternatives, separator }, rest.split_at(1).1))
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn const_parse_like_parse_with() {
+	use alloc::vec::Vec;
+	use crate::u8_buf::*;
+
+	for syntax in [Syntax::<u8>::GLOB, Syntax::<u8>::PATH_GLOB, Syntax::wildcard(b'*')] {
+		for pattern in [
+			b"".as_ref(), b"*", b"**", b"a", b"*.nav*", b"cp_*_b?.bsp", b"[!a-z]**", b"a**b", b"[]][a", b"[a",
+			b"a\\*b", b"*\\*", b"\\\\*", b"\\[a]\\", b"maps/**/*.bsp", b"**/sound/**", b"a/**b", b"/**/", b"**",
+			b"*.{vmt,vtf}", b"{a}{,b}", b"{a,b", b"\\{a,b}", b"[{]a,b}", b"maps/{**,x}/{}",
+		] {
+			let expected = Pattern::<U8Pieces, u8>::parse_with(pattern, &syntax);
+			let (flags, mut tokens) = ConstTokens::new(pattern, &syntax);
+			let mut pieces = Vec::new();
+			while let Some(piece) = tokens.next() {
+				pieces.push(piece);
+			}
+			assert_eq!(syntax.piece_count(pattern), pieces.len());
+			assert_eq!(Pattern::new(pieces.into_iter().collect(), flags), expected, "{pattern:?}");
+		}
+	}
+
+	const MAPS: &[Pattern<&[Piece<u8>], u8>] = &[
+		pattern!("cp_*_b?.bsp"),
+		pattern!("maps/**/*.nav", Syntax::<u8>::PATH_GLOB),
+	];
+	assert!(MAPS[0].first_match(crate::ExactMatch, b"cp_badlands_b4.bsp").is_some());
+	assert!(MAPS[1].first_match(crate::PathMatch, b"maps/workshop/cp_well.nav").is_some());
+	assert!(MAPS[1].first_match(crate::PathMatch, b"maps/workshop/cp_well.bsp").is_none());
+}
@@ -2,75 +2,462 @@
 
 #![no_std]
 
+use core::ops::Range;
+
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
 #[cfg(feature = "alloc")]
 pub mod u8_buf;
 
+#[cfg(feature = "alloc")]
+pub mod vec_buf;
+
+#[cfg(feature = "alloc")]
+mod rule_set;
+#[cfg(feature = "alloc")]
+pub use rule_set::*;
+
+#[cfg(feature = "alloc")]
+mod pattern_set;
+#[cfg(feature = "alloc")]
+pub use pattern_set::*;
+
 mod pattern;
 pub use pattern::*;
 
-pub fn matches<'a, P, M, T: 'a>(pattern: P, matcher: M, haystack: &[T]) -> Option<&[T]>
+mod condition;
+pub use condition::*;
+
+mod syntax;
+pub use syntax::*;
+
+mod const_parse;
+
+#[cfg(feature = "unicode")]
+mod unicode;
+#[cfg(feature = "unicode")]
+pub use unicode::*;
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub fn matches<'a, P, M, T: 'a + PartialEq>(pattern: P, matcher: M, haystack: &[T]) -> Option<&[T]>
 where
 	P: IntoIterator<Item = &'a [T]>,
+	P::IntoIter: Clone,
 	M: Matcher<T>,
 {
-	matches_impl(pattern.into_iter(), matcher, haystack)
+	matches_impl(WildcardSeparated::new(pattern.into_iter()), matcher, haystack, PatternFlags::empty(), &mut [])
 }
 
-pub fn suffix_matches<'a, P, M, T: 'a>(pattern: P, matcher: M, haystack: &[T]) -> Option<&[T]>
+pub fn suffix_matches<'a, P, M, T: 'a + PartialEq>(pattern: P, matcher: M, haystack: &[T]) -> Option<&[T]>
 where
 	P: IntoIterator<Item = &'a [T]>,
+	P::IntoIter: Clone,
+	M: Matcher<T>,
+{
+	suffix_matches_impl(WildcardSeparated::new(pattern.into_iter()), matcher, haystack, PatternFlags::empty(), &mut [])
+}
+
+/// Result of matching the rest of a pattern against the rest of a haystack.
+enum Outcome<'h, T> {
+	/// The pattern matched, leaving the contained remainder of the haystack.
+	Match(&'h [T]),
+	/// The pattern didn't match, but may match if an earlier wildcard consumed more of the haystack.
+	Mismatch,
+	/// The pattern can't match, no matter how much of the haystack earlier wildcards consume.
+	Exhausted,
+}
+
+impl<'h, T> Outcome<'h, T> {
+	#[inline]
+	fn into_option(self) -> Option<&'h [T]> {
+		match self {
+			Self::Match(rest) => Some(rest),
+			Self::Mismatch | Self::Exhausted => None,
+		}
+	}
+}
+
+/// What a wildcard piece may consume, given the flags of its pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WildcardKind {
+	/// Any sequence of elements.
+	Any,
+	/// Any sequence of elements without separators.
+	Segment,
+	/// Any sequence of whole segments, each followed by a separator.
+	Segments,
+}
+
+impl WildcardKind {
+	const fn of_wildcard(flags: PatternFlags) -> Self {
+		if flags.is_path_aware() { Self::Segment } else { Self::Any }
+	}
+
+	const fn of_globstar(flags: PatternFlags) -> Self {
+		if flags.is_path_aware() { Self::Segments } else { Self::Any }
+	}
+
+	fn of<T>(piece: &Piece<'_, T>, flags: PatternFlags) -> Option<Self> {
+		match piece {
+			Piece::Wildcard => Some(Self::of_wildcard(flags)),
+			Piece::Globstar => Some(Self::of_globstar(flags)),
+			_ => None,
+		}
+	}
+}
+
+/// Parts of a match that stay the same while backtracking.
+struct Context<'m, 'c, M> {
+	matcher: &'m M,
+	flags: PatternFlags,
+	/// Length of the whole haystack, of which every haystack being matched is a suffix.
+	haystack_len: usize,
+	/// Spans of the whole haystack matched by the wildcards so far, at the indices of the wildcards.
+	captures: &'c mut [Range<usize>],
+	/// Whether the haystack is only the start of one, so that running out of it is a match.
+	is_prefix: bool,
+}
+
+impl<M> Context<'_, '_, M> {
+	/// Record that the wildcard at `index` matched the first `len` elements of `haystack`.
+	#[inline]
+	fn capture<T>(&mut self, index: usize, haystack: &[T], len: usize) {
+		if let Some(capture) = self.captures.get_mut(index) {
+			let start = self.haystack_len - haystack.len();
+			*capture = start..start + len;
+		}
+	}
+}
+
+fn matches_impl<'a, 'h, P, M, T: 'a + PartialEq>(
+	pattern: P, matcher: M, haystack: &'h [T], flags: PatternFlags, captures: &mut [Range<usize>],
+) -> Option<&'h [T]>
+where
+	P: Iterator<Item = Piece<'a, T>> + Clone,
+	M: Matcher<T>,
+{
+	if pattern.clone().next().is_none() {
+		return haystack.is_empty().then_some(haystack)
+	}
+	let mut cx = Context { matcher: &matcher, flags, haystack_len: haystack.len(), captures, is_prefix: false };
+	match_here(pattern, &mut cx, haystack, 0).into_option()
+}
+
+fn suffix_matches_impl<'a, 'h, P, M, T: 'a + PartialEq>(
+	pattern: P, matcher: M, haystack: &'h [T], flags: PatternFlags, captures: &mut [Range<usize>],
+) -> Option<&'h [T]>
+where
+	P: Iterator<Item = Piece<'a, T>> + Clone,
 	M: Matcher<T>,
 {
-	suffix_matches_impl(pattern.into_iter(), matcher, haystack)
+	let mut cx = Context { matcher: &matcher, flags, haystack_len: haystack.len(), captures, is_prefix: false };
+	after_wildcard(WildcardKind::of_wildcard(flags), pattern, &mut cx, haystack, 0).into_option()
 }
 
-fn matches_impl<'a, P, M, T: 'a>(mut pattern: P, matcher: M, mut haystack: &[T]) -> Option<&[T]>
+/// Returns `true` if `pattern` matches some haystack that starts with `prefix`.
+fn is_prefix_match_impl<'a, P, M, T: 'a + PartialEq>(pattern: P, matcher: M, prefix: &[T], flags: PatternFlags) -> bool
 where
-	P: Iterator<Item = &'a [T]>,
+	P: Iterator<Item = Piece<'a, T>> + Clone,
 	M: Matcher<T>,
 {
-	match pattern.next() {
-		Some(first) => {
-			haystack = haystack.split_at_checked(first.len())
-				.and_then(|(window, haystack)| matcher.is_equal(first, window).then_some(haystack))?;
+	let mut cx = Context { matcher: &matcher, flags, haystack_len: prefix.len(), captures: &mut [], is_prefix: true };
+	let outcome = if flags.is_start_unanchored() {
+		after_wildcard(WildcardKind::of_wildcard(flags), pattern, &mut cx, prefix, 0)
+	} else {
+		match_here(pattern, &mut cx, prefix, 0)
+	};
+	outcome.into_option().is_some()
+}
+
+/// Find the leftmost span of `haystack` that `pattern` matches, starting at `from` or later.
+///
+/// The wildcards implied by `flags` at the start and end of the pattern are ignored, as a span can start and end
+/// anywhere, and wildcards match as little as possible.
+fn find_impl<'a, P, M, T: 'a + PartialEq>(
+	pattern: P, matcher: M, haystack: &[T], flags: PatternFlags, from: usize,
+) -> Option<Range<usize>>
+where
+	P: Iterator<Item = Piece<'a, T>> + Clone,
+	M: Matcher<T>,
+{
+	let flags = if flags.is_path_aware() { PatternFlags::empty().with_path_aware() } else { PatternFlags::empty() };
+	let mut cx = Context { matcher: &matcher, flags, haystack_len: haystack.len(), captures: &mut [], is_prefix: false };
+	let first = pattern.clone().next();
+	let first_literal = match first {
+		Some(Piece::Literal(literal)) => Some(literal),
+		_ => None,
+	};
+	// Starting later is the same as a leading wildcard consuming more, but other pieces may be exhausted by what's
+	// before them in the span, which a later start skips.
+	let is_exhaustible = matches!(first, Some(Piece::Wildcard));
+	let mut start = from;
+	while start <= haystack.len() {
+		if let Some(literal) = first_literal {
+			start += find_literal(literal, &matcher, &haystack[start..])?;
+		}
+		match match_here(pattern.clone(), &mut cx, &haystack[start..], 0) {
+			Outcome::Match(rest) => return Some(start..haystack.len() - rest.len()),
+			Outcome::Exhausted if is_exhaustible => return None,
+			Outcome::Mismatch | Outcome::Exhausted => start += 1,
 		}
-		None => return haystack.is_empty().then_some(haystack)
 	}
-	suffix_matches_impl(pattern, matcher, haystack)
+	None
 }
 
-fn suffix_matches_impl<'a, P, M, T: 'a>(pattern: P, matcher: M, mut haystack: &[T]) -> Option<&[T]>
+/// Match `pattern` against the start of `haystack`, with the first wildcard in `pattern` at index `capture`.
+fn match_here<'a, 'h, P, M, T: 'a + PartialEq>(
+	mut pattern: P, cx: &mut Context<'_, '_, M>, mut haystack: &'h [T], mut capture: usize,
+) -> Outcome<'h, T>
 where
-	P: Iterator<Item = &'a [T]>,
+	P: Iterator<Item = Piece<'a, T>> + Clone,
 	M: Matcher<T>,
 {
-	for piece in pattern {
-		let piece_len = piece.len();
-		if piece_len > 0 {
-			let new_haystack = haystack.windows(piece_len)
-				.position(|window| matcher.is_equal(piece, window))
-				.and_then(move |offset| haystack.get(offset + piece_len..))?;
-			/*
-			let new_haystack = memchr::memmem::find(haystack, piece)
-				.and_then(move |offset| haystack.get(offset + piece_len..));
-			*/
-			haystack = new_haystack;
+	let (matcher, flags) = (cx.matcher, cx.flags);
+	// In path-aware patterns, only literals match separators.
+	let next_element = move |haystack: &'h [T]| match haystack.first() {
+		Some(t) if !(flags.is_path_aware() && matcher.is_separator(t)) => {
+			Some(haystack.split_at(matcher.element_len(haystack)))
+		}
+		_ => None,
+	};
+	loop {
+		// The rest of the pattern may match whatever follows a prefix.
+		if cx.is_prefix && haystack.is_empty() {
+			return Outcome::Match(haystack)
+		}
+		let rest = match pattern.next() {
+			Some(Piece::Literal(literal)) => {
+				match haystack.split_at_checked(literal.len()) {
+					Some((window, rest)) if matcher.is_equal(literal, window) => haystack = rest,
+					None if cx.is_prefix && matcher.is_equal(&literal[..haystack.len()], haystack) => {
+						return Outcome::Match(&haystack[haystack.len()..])
+					}
+					_ => return Outcome::Mismatch,
+				}
+				continue
+			}
+			Some(Piece::Any) => next_element(haystack).map(move |(_, rest)| rest),
+			Some(Piece::Class(class)) => next_element(haystack)
+				.and_then(|(t, rest)| class.accepts(matcher, t).then_some(rest)),
+			Some(Piece::Alternation(alternation)) => {
+				return match_alternation(alternation, pattern, cx, haystack, capture)
+			}
+			Some(Piece::Wildcard) => {
+				return after_wildcard(WildcardKind::of_wildcard(flags), pattern, cx, haystack, capture)
+			}
+			Some(Piece::Globstar) => {
+				return after_wildcard(WildcardKind::of_globstar(flags), pattern, cx, haystack, capture)
+			}
+			None if !flags.is_end_anchored() => {
+				cx.capture(capture, haystack, haystack.len());
+				return Outcome::Match(haystack)
+			}
+			None if haystack.is_empty() => return Outcome::Match(haystack),
+			None => return Outcome::Mismatch,
+		};
+		match rest {
+			Some(rest) => {
+				cx.capture(capture, haystack, haystack.len() - rest.len());
+				capture += 1;
+				haystack = rest;
+			}
+			None => return Outcome::Mismatch,
 		}
 	}
-	Some(haystack)
+}
+
+/// Match `pattern` against `haystack`, with a wildcard of `kind` at index `capture` before `pattern`.
+///
+/// Positions are tried from the leftmost one, so that the wildcard consumes as little as possible. When `pattern`
+/// continues with a literal, only the positions where that literal occurs are tried.
+fn after_wildcard<'a, 'h, P, M, T: 'a + PartialEq>(
+	kind: WildcardKind, mut pattern: P, cx: &mut Context<'_, '_, M>, haystack: &'h [T], capture: usize,
+) -> Outcome<'h, T>
+where
+	P: Iterator<Item = Piece<'a, T>> + Clone,
+	M: Matcher<T>,
+{
+	let matcher = cx.matcher;
+
+	// Consecutive wildcards of the same kind match the same as a single one, so the others match nothing.
+	let mut skipped = 0;
+	let next = loop {
+		let mut rest = pattern.clone();
+		match rest.next() {
+			Some(piece) if WildcardKind::of(&piece, cx.flags) == Some(kind) => {
+				pattern = rest;
+				skipped += 1;
+			}
+			next => break next,
+		}
+	};
+
+	// Positions past `limit` would make the wildcard consume a separator.
+	let limit = match kind {
+		WildcardKind::Segment => haystack.iter().position(move |t| matcher.is_separator(t)).unwrap_or(haystack.len()),
+		WildcardKind::Any | WildcardKind::Segments => haystack.len(),
+	};
+	let mut start = 0;
+	loop {
+		let position = match (kind, next) {
+			(WildcardKind::Segments, _) => (start..=haystack.len())
+				.find(move |&i| i == 0 || matcher.is_separator(&haystack[i - 1])),
+			// The literal may only start in a prefix, so it can't be searched for.
+			(_, Some(Piece::Literal(literal))) if !cx.is_prefix => haystack.get(start..)
+				.and_then(|haystack| find_literal(literal, matcher, haystack))
+				.map(move |offset| start + offset),
+			_ => Some(start),
+		};
+		let Some(position) = position.filter(move |&position| position <= limit) else {
+			// Unless some positions weren't tried, earlier wildcards can't help by consuming more.
+			return match kind {
+				WildcardKind::Any => Outcome::Exhausted,
+				WildcardKind::Segment if limit == haystack.len() => Outcome::Exhausted,
+				WildcardKind::Segment | WildcardKind::Segments => Outcome::Mismatch,
+			}
+		};
+		cx.capture(capture, haystack, position);
+		for skipped in 1..=skipped {
+			cx.capture(capture + skipped, &haystack[position..], 0);
+		}
+		match match_here(pattern.clone(), cx, &haystack[position..], capture + skipped + 1) {
+			Outcome::Mismatch => start = position + 1,
+			outcome => return outcome,
+		}
+	}
+}
+
+/// Match `pattern` against `haystack`, with an alternation at index `capture` before `pattern`.
+///
+/// Alternatives are tried in order, each followed by the rest of the pattern, so that the first one that leads to a
+/// match is used.
+fn match_alternation<'a, 'h, P, M, T: 'a + PartialEq>(
+	alternation: Alternation<'a, T>, pattern: P, cx: &mut Context<'_, '_, M>, haystack: &'h [T], capture: usize,
+) -> Outcome<'h, T>
+where
+	P: Iterator<Item = Piece<'a, T>> + Clone,
+	M: Matcher<T>,
+{
+	let matcher = cx.matcher;
+	// Earlier wildcards can only help if an alternative doesn't match here, or if the rest of the pattern could match
+	// after one.
+	let mut outcome = Outcome::Exhausted;
+	for alternative in alternation.alternatives() {
+		let rest = match haystack.split_at_checked(alternative.len()) {
+			Some((window, rest)) if matcher.is_equal(alternative, window) => rest,
+			None if cx.is_prefix && matcher.is_equal(&alternative[..haystack.len()], haystack) => {
+				return Outcome::Match(&haystack[haystack.len()..])
+			}
+			_ => {
+				outcome = Outcome::Mismatch;
+				continue
+			}
+		};
+		cx.capture(capture, haystack, alternative.len());
+		match match_here(pattern.clone(), cx, rest, capture + 1) {
+			Outcome::Match(rest) => return Outcome::Match(rest),
+			Outcome::Mismatch => outcome = Outcome::Mismatch,
+			Outcome::Exhausted => {}
+		}
+	}
+	outcome
+}
+
+fn find_literal<T, M: Matcher<T>>(literal: &[T], matcher: &M, haystack: &[T]) -> Option<usize> {
+	if literal.is_empty() {
+		return Some(0)
+	}
+	matcher.find(literal, haystack)
+}
+
+/// Find the first window of `haystack` with the length `needle_len` that starts with `first` or `second`, and that
+/// `is_equal` accepts.
+fn find_by_first_byte<F: Fn(&[u8]) -> bool>(
+	needle_len: usize, haystack: &[u8], first: u8, second: u8, is_equal: F,
+) -> Option<usize> {
+	#[cfg(feature = "memchr")]
+	{
+		let last_start = haystack.len().checked_sub(needle_len)?;
+		memchr::memchr2_iter(first, second, &haystack[..=last_start])
+			.find(move |&start| is_equal(&haystack[start..start + needle_len]))
+	}
+	#[cfg(not(feature = "memchr"))]
+	{
+		let _ = (first, second);
+		haystack.windows(needle_len).position(is_equal)
+	}
 }
 
 pub trait Matcher<T> {
 	fn is_equal(&self, a: &[T], b: &[T]) -> bool;
+
+	/// Find the start of the first window of `haystack` that's equal to `needle`, which isn't empty.
+	fn find(&self, needle: &[T], haystack: &[T]) -> Option<usize> {
+		haystack.windows(needle.len()).position(move |window| self.is_equal(needle, window))
+	}
+
+	/// Get the number of elements at the start of `haystack`, which isn't empty, that a [`Piece::Any`] or
+	/// [`Piece::Class`] matches as one, such as the bytes of a character in UTF-8.
+	///
+	/// By default, each element is matched on its own.
+	fn element_len(&self, haystack: &[T]) -> usize {
+		let _ = haystack;
+		1
+	}
+
+	/// Returns `true` if `t` is in the inclusive range from `low` to `high`, for a [`Class`], each of them being
+	/// elements that a [`Piece::Class`] matches as one, as split by [`element_len`](Self::element_len).
+	///
+	/// By default, only `low` and `high` themselves are in the range, as elements can't be ordered in general.
+	fn is_in_range(&self, low: &[T], high: &[T], t: &[T]) -> bool {
+		self.is_equal(low, t) || self.is_equal(high, t)
+	}
+
+	/// Returns `true` if `t` separates the segments of a path, for a path-aware [`Pattern`].
+	fn is_separator(&self, t: &T) -> bool {
+		let _ = t;
+		false
+	}
 }
 
 impl<T, M: Matcher<T>> Matcher<T> for &M {
 	fn is_equal(&self, a: &[T], b: &[T]) -> bool {
 		Matcher::is_equal(*self, a, b)
 	}
+
+	fn find(&self, needle: &[T], haystack: &[T]) -> Option<usize> {
+		Matcher::find(*self, needle, haystack)
+	}
+
+	fn element_len(&self, haystack: &[T]) -> usize {
+		Matcher::element_len(*self, haystack)
+	}
+
+	fn is_in_range(&self, low: &[T], high: &[T], t: &[T]) -> bool {
+		Matcher::is_in_range(*self, low, high, t)
+	}
+
+	fn is_separator(&self, t: &T) -> bool {
+		Matcher::is_separator(*self, t)
+	}
+}
+
+/// [`Matcher`] that compares elements one at a time, so that equal elements can be found by their key.
+pub trait KeyMatcher<T>: Matcher<T> {
+	type Key: Ord + Clone;
+
+	/// Get the key of `t`, which must be the same as that of every element that `t` is equal to.
+	fn key(&self, t: &T) -> Self::Key;
+}
+
+impl<T, M: KeyMatcher<T>> KeyMatcher<T> for &M {
+	type Key = M::Key;
+	fn key(&self, t: &T) -> Self::Key {
+		KeyMatcher::key(*self, t)
+	}
 }
 
 /*
@@ -81,6 +468,10 @@ impl<F: Fn(&[u8], &[u8]) -> bool> Matcher for F {
 }
 */
 
+/// [`Matcher`] that compares elements with `==`.
+///
+/// It's generic over the elements, so it finds literals by comparing each window, even with the `memchr` feature.
+/// [`ByteMatch`] matches bytes the same way, but finds literals with `memchr`.
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ExactMatch;
 impl<T: PartialEq> Matcher<T> for ExactMatch {
@@ -89,12 +480,102 @@ impl<T: PartialEq> Matcher<T> for ExactMatch {
 	}
 }
 
+impl<T: Ord + Clone> KeyMatcher<T> for ExactMatch {
+	type Key = T;
+	fn key(&self, t: &T) -> Self::Key {
+		t.clone()
+	}
+}
+
+/// [`ExactMatch`] for elements that can be ordered, so that a [`Class`] can contain ranges such as `[a-z]`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrdMatch;
+impl<T: PartialOrd> Matcher<T> for OrdMatch {
+	fn is_equal(&self, a: &[T], b: &[T]) -> bool {
+		a == b
+	}
+
+	fn is_in_range(&self, low: &[T], high: &[T], t: &[T]) -> bool {
+		single_elements(low, high, t).is_some_and(move |(low, high, t)| low <= t && t <= high)
+	}
+}
+
+impl<T: Ord + Clone> KeyMatcher<T> for OrdMatch {
+	type Key = T;
+	fn key(&self, t: &T) -> Self::Key {
+		t.clone()
+	}
+}
+
+/// [`OrdMatch`] for bytes, which finds literals with `memchr` if the feature is enabled.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteMatch;
+impl Matcher<u8> for ByteMatch {
+	fn is_equal(&self, a: &[u8], b: &[u8]) -> bool {
+		a == b
+	}
+
+	fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+		#[cfg(feature = "memchr")]
+		{
+			memchr::memmem::find(haystack, needle)
+		}
+		#[cfg(not(feature = "memchr"))]
+		{
+			haystack.windows(needle.len()).position(move |window| window == needle)
+		}
+	}
+
+	fn is_in_range(&self, low: &[u8], high: &[u8], t: &[u8]) -> bool {
+		single_elements(low, high, t).is_some_and(move |(low, high, t)| (*low..=*high).contains(t))
+	}
+}
+
+impl KeyMatcher<u8> for ByteMatch {
+	type Key = u8;
+	fn key(&self, t: &u8) -> Self::Key {
+		*t
+	}
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CaseInsensitive;
 impl Matcher<u8> for CaseInsensitive {
 	fn is_equal(&self, a: &[u8], b: &[u8]) -> bool {
 		a.eq_ignore_ascii_case(b)
 	}
+
+	fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+		let first = needle[0];
+		find_by_first_byte(
+			needle.len(), haystack, first.to_ascii_lowercase(), first.to_ascii_uppercase(),
+			move |window| window.eq_ignore_ascii_case(needle),
+		)
+	}
+
+	fn is_in_range(&self, low: &[u8], high: &[u8], t: &[u8]) -> bool {
+		single_elements(low, high, t).is_some_and(move |(low, high, t)| is_in_range_ignore_ascii_case(*low, *high, *t))
+	}
+}
+
+impl KeyMatcher<u8> for CaseInsensitive {
+	type Key = u8;
+	fn key(&self, t: &u8) -> Self::Key {
+		t.to_ascii_lowercase()
+	}
+}
+
+/// Get the only element of each of `low`, `high` and `t`, for ranges of matchers that match elements one at a time.
+fn single_elements<'a, T>(low: &'a [T], high: &'a [T], t: &'a [T]) -> Option<(&'a T, &'a T, &'a T)> {
+	match (low, high, t) {
+		([low], [high], [t]) => Some((low, high, t)),
+		_ => None,
+	}
+}
+
+fn is_in_range_ignore_ascii_case(low: u8, high: u8, t: u8) -> bool {
+	let range = low..=high;
+	range.contains(&t) || range.contains(&t.to_ascii_lowercase()) || range.contains(&t.to_ascii_uppercase())
 }
 
 #[test]
@@ -132,6 +613,24 @@ fn case_insensitive_match() {
 	assert_eq!(suffix_matches(pattern, CaseInsensitive, b"DM_FLOOD.NAV"), Some(b"".as_ref()));
 }
 
+#[test]
+fn find_like_windows() {
+	fn assert_finds_like_windows<M: Matcher<u8>>(matcher: M, needle: &[u8], haystack: &[u8]) {
+		let expected = haystack.windows(needle.len()).position(|window| matcher.is_equal(needle, window));
+		assert_eq!(matcher.find(needle, haystack), expected, "{needle:?} in {haystack:?}");
+	}
+	let haystack = b"Maps\\workshop/CP_Dustbowl.bsp";
+	for needle in [b"p".as_ref(), b"/", b"\\", b"s/", b"cp_", b"CP_D", b".bsp", b"bsp.", b"Maps/workshop\\cp"] {
+		assert_finds_like_windows(ExactMatch, needle, haystack);
+		assert_finds_like_windows(ByteMatch, needle, haystack);
+		assert_finds_like_windows(ByteMatch, needle, b"");
+		assert_finds_like_windows(CaseInsensitive, needle, haystack);
+		assert_finds_like_windows(PathMatch, needle, haystack);
+		assert_finds_like_windows(PathMatch, needle, b"");
+		assert_finds_like_windows(ExactPathMatch, needle, haystack);
+	}
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PathMatch;
 impl Matcher<u8> for PathMatch {
@@ -146,6 +645,78 @@ impl Matcher<u8> for PathMatch {
 		}
 		true
 	}
+
+	fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+		let (first, second) = match needle[0] {
+			b'/' | b'\\' => (b'/', b'\\'),
+			first => (first.to_ascii_lowercase(), first.to_ascii_uppercase()),
+		};
+		find_by_first_byte(needle.len(), haystack, first, second, move |window| self.is_equal(needle, window))
+	}
+
+	fn is_in_range(&self, low: &[u8], high: &[u8], t: &[u8]) -> bool {
+		single_elements(low, high, t).is_some_and(move |(low, high, t)| match t {
+			b'/' | b'\\' => (*low..=*high).contains(&b'/') || (*low..=*high).contains(&b'\\'),
+			t => is_in_range_ignore_ascii_case(*low, *high, *t),
+		})
+	}
+
+	fn is_separator(&self, t: &u8) -> bool {
+		matches!(t, b'/' | b'\\')
+	}
+}
+
+impl KeyMatcher<u8> for PathMatch {
+	type Key = u8;
+	fn key(&self, t: &u8) -> Self::Key {
+		match t {
+			b'\\' => b'/',
+			t => t.to_ascii_lowercase(),
+		}
+	}
+}
+
+/// [`PathMatch`], without ignoring case, for filesystems on which `Maps/a.bsp` and `maps/a.bsp` are different files.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExactPathMatch;
+impl Matcher<u8> for ExactPathMatch {
+	fn is_equal(&self, a: &[u8], b: &[u8]) -> bool {
+		a.len() == b.len() && a.iter().zip(b).all(move |(a, b)| a == b || matches!((a, b), (b'/', b'\\') | (b'\\', b'/')))
+	}
+
+	fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+		// Without separators, only equal windows match.
+		#[cfg(feature = "memchr")]
+		if !needle.iter().any(move |t| self.is_separator(t)) {
+			return memchr::memmem::find(haystack, needle)
+		}
+		let (first, second) = match needle[0] {
+			b'/' | b'\\' => (b'/', b'\\'),
+			first => (first, first),
+		};
+		find_by_first_byte(needle.len(), haystack, first, second, move |window| self.is_equal(needle, window))
+	}
+
+	fn is_in_range(&self, low: &[u8], high: &[u8], t: &[u8]) -> bool {
+		single_elements(low, high, t).is_some_and(move |(low, high, t)| match t {
+			b'/' | b'\\' => (*low..=*high).contains(&b'/') || (*low..=*high).contains(&b'\\'),
+			t => (*low..=*high).contains(t),
+		})
+	}
+
+	fn is_separator(&self, t: &u8) -> bool {
+		matches!(t, b'/' | b'\\')
+	}
+}
+
+impl KeyMatcher<u8> for ExactPathMatch {
+	type Key = u8;
+	fn key(&self, t: &u8) -> Self::Key {
+		match t {
+			b'\\' => b'/',
+			t => *t,
+		}
+	}
 }
 
 #[test]
@@ -156,3 +727,12 @@ fn path_match() {
 	assert_eq!(matches(pattern, PathMatch, b"maps/cp_dustbowl.nav"), Some(b"".as_ref()));
 	assert_eq!(matches(pattern, PathMatch, b"maps/cp_dustbowl.bsp"), None);
 }
+
+#[test]
+fn exact_path_match() {
+	let pattern = [b"maps/".as_ref(), b".nav"];
+	assert_eq!(matches(pattern, ExactPathMatch, b"maps\\cp_dustbowl.nav"), Some(b"".as_ref()));
+	assert_eq!(matches(pattern, ExactPathMatch, b"Maps/cp_dustbowl.nav"), None);
+	assert_eq!(matches(pattern, ExactPathMatch, b"maps/DM_FLOOD.NAV"), None);
+	assert_eq!(ExactPathMatch.key(&b'\\'), ExactPathMatch.key(&b'/'));
+}
@@ -1,8 +1,18 @@
-use core::marker::PhantomData;
+#[cfg(feature = "alloc")]
+use alloc::{
+	vec, vec::Vec,
+};
+use core::{
+	fmt,
+	iter::Copied,
+	marker::PhantomData,
+	ops::Range,
+	slice,
+};
 
 use crate::{
-	Matcher,
-	matches_impl, suffix_matches_impl,
+	Matcher, Syntax,
+	find_impl, is_prefix_match_impl, matches_impl, suffix_matches_impl,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -22,46 +32,196 @@ impl<T, P: Pieces<T>> Pattern<P, T> {
 		}
 	}
 
-	pub fn first_match<'a, M: Matcher<T>>(&self, matcher: M, haystack: &'a [T]) -> Option<&'a [T]> {
-		let rest = if self.flags.is_start_unanchored() {
-			suffix_matches_impl(self.pieces.pieces(), matcher, haystack)
+	pub fn first_match<'a, M: Matcher<T>>(&self, matcher: M, haystack: &'a [T]) -> Option<&'a [T]>
+	where
+		T: PartialEq,
+	{
+		self.first_match_captures(matcher, haystack, &mut [])
+	}
+
+	/// Match like [`first_match`](Self::first_match), and store the span of `haystack` matched by each wildcard in
+	/// `captures`, at the index of the wildcard.
+	///
+	/// Every piece other than a [`Piece::Literal`] is a wildcard, and so are the wildcards implied by
+	/// [`PatternFlags`] at the start and end of the pattern. Wildcards past the end of `captures` aren't stored, and
+	/// `captures` may be changed even if the pattern doesn't match.
+	pub fn first_match_captures<'a, M: Matcher<T>>(
+		&self, matcher: M, haystack: &'a [T], captures: &mut [Range<usize>],
+	) -> Option<&'a [T]>
+	where
+		T: PartialEq,
+	{
+		if self.flags.is_start_unanchored() {
+			suffix_matches_impl(self.pieces.pieces(), matcher, haystack, self.flags, captures)
 		} else {
-			matches_impl(self.pieces.pieces(), matcher, haystack)
-		};
-		rest.filter(move |rest| !self.flags.is_end_anchored() || rest.is_empty())
+			matches_impl(self.pieces.pieces(), matcher, haystack, self.flags, captures)
+		}
+	}
+
+	/// Find the leftmost span of `haystack` that the pattern matches.
+	///
+	/// Unlike [`first_match`](Self::first_match), the span can start and end anywhere in `haystack`, so the
+	/// wildcards implied by [`PatternFlags`] at the start and end of the pattern are ignored. Wildcards match as
+	/// little as possible, so `maps/*.bsp` finds `maps/a.bsp` in `maps/a.bsp maps/b.bsp`.
+	pub fn find<M: Matcher<T>>(&self, matcher: M, haystack: &[T]) -> Option<Range<usize>>
+	where
+		T: PartialEq,
+	{
+		find_impl(self.pieces.pieces(), matcher, haystack, self.flags, 0)
+	}
+
+	/// Returns `true` if the pattern matches some haystack that starts with `prefix`, such as a path in the directory
+	/// that `prefix` is the path of.
+	///
+	/// Every element after `prefix` is assumed to be possible, even where no element could match, so this may
+	/// return `true` for some patterns that can't match anything.
+	pub fn is_prefix_match<M: Matcher<T>>(&self, matcher: M, prefix: &[T]) -> bool
+	where
+		T: PartialEq,
+	{
+		is_prefix_match_impl(self.pieces.pieces(), matcher, prefix, self.flags)
+	}
+
+	/// Iterate over the spans of `haystack` that the pattern matches, from left to right, without overlapping.
+	///
+	/// Each span is found like [`find`](Self::find) finds the first one.
+	pub fn find_iter<'p, 'h, M: Matcher<T>>(&'p self, matcher: M, haystack: &'h [T]) -> FindIter<'p, 'h, P, T, M> {
+		FindIter {
+			pattern: self,
+			matcher,
+			haystack,
+			start: 0,
+		}
+	}
+
+	/// Get the number of wildcards whose spans are stored by [`first_match_captures`](Self::first_match_captures).
+	pub fn capture_count(&self) -> usize {
+		let pieces = self.pieces.pieces().filter(move |piece| !matches!(piece, Piece::Literal(_))).count();
+		usize::from(self.flags.is_start_unanchored()) + pieces + usize::from(!self.flags.is_end_anchored())
 	}
 }
 
-impl<'a, T: 'a + PartialEq, P: FromIterator<&'a [T]>> Pattern<P, T> {
-	pub fn parse(pattern: &'a [T], wildcard: &T) -> Self {
-		let mut flags = PatternFlags::empty();
-		if pattern.first() == Some(wildcard) {
-			flags = flags.with_start_unanchored();
+#[cfg(feature = "alloc")]
+impl<T, P: Pieces<T>> Pattern<P, T> {
+	/// Get the spans of `haystack` matched by the wildcards of the pattern, if it matches.
+	///
+	/// See [`first_match_captures`](Self::first_match_captures).
+	pub fn captures<M: Matcher<T>>(&self, matcher: M, haystack: &[T]) -> Option<Vec<Range<usize>>>
+	where
+		T: PartialEq,
+	{
+		let mut captures = vec![0..0; self.capture_count()];
+		self.first_match_captures(matcher, haystack, &mut captures)?;
+		Some(captures)
+	}
+
+	/// Build a sequence from the pattern as a template, in which each wildcard is replaced by the span of `haystack`
+	/// in `captures` at its index, and each literal is copied.
+	///
+	/// Wildcards without a span in `captures` are replaced with nothing.
+	pub fn substitute(&self, haystack: &[T], captures: &[Range<usize>]) -> Vec<T>
+	where
+		T: Clone,
+	{
+		let mut captures = captures.iter().map(move |capture| &haystack[capture.clone()]);
+		let mut result = Vec::new();
+		if self.flags.is_start_unanchored() {
+			result.extend_from_slice(captures.next().unwrap_or_default());
 		}
-		if pattern.last() != Some(wildcard) {
-			flags = flags.with_end_anchored();
+		for piece in self.pieces.pieces() {
+			match piece {
+				Piece::Literal(literal) => result.extend_from_slice(literal),
+				_ => result.extend_from_slice(captures.next().unwrap_or_default()),
+			}
 		}
+		if !self.flags.is_end_anchored() {
+			result.extend_from_slice(captures.next().unwrap_or_default());
+		}
+		result
+	}
 
-		Self {
-			flags,
-			pieces: pattern.split(move |t| t == wildcard).collect(),
-			piece_t: PhantomData,
+	/// Match `haystack` against the pattern, and if it matches, build a sequence from `template` with its wildcards
+	/// replaced by what the wildcards of the pattern matched, in order.
+	///
+	/// For example, `maps/*.bsp` with the template `maps/archive/*.bsp` turns `maps/a.bsp` into
+	/// `maps/archive/a.bsp`.
+	pub fn rewrite<M: Matcher<T>, Q: Pieces<T>>(
+		&self, matcher: M, haystack: &[T], template: &Pattern<Q, T>,
+	) -> Option<Vec<T>>
+	where
+		T: Clone + PartialEq,
+	{
+		let captures = self.captures(matcher, haystack)?;
+		Some(template.substitute(haystack, &captures))
+	}
+}
+
+impl<'a, T: 'a + Clone + PartialEq, P: FromIterator<Piece<'a, T>>> Pattern<P, T> {
+	/// Parse `pattern`, in which `wildcard` is the only element with a special meaning.
+	pub fn parse(pattern: &'a [T], wildcard: &T) -> Self {
+		Self::parse_with(pattern, &Syntax::wildcard(wildcard.clone()))
+	}
+}
+
+/// Iterator over the spans of a haystack that a [`Pattern`] matches, returned by [`Pattern::find_iter`].
+#[derive(Debug, Clone)]
+pub struct FindIter<'p, 'h, P, T, M> {
+	pattern: &'p Pattern<P, T>,
+	matcher: M,
+	haystack: &'h [T],
+	/// Where the next span may start, or past the end of `haystack` once there are no more spans.
+	start: usize,
+}
+
+impl<T: PartialEq, P: Pieces<T>, M: Matcher<T>> Iterator for FindIter<'_, '_, P, T, M> {
+	type Item = Range<usize>;
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.start > self.haystack.len() {
+			return None
 		}
+		let found = find_impl(self.pattern.pieces.pieces(), &self.matcher, self.haystack, self.pattern.flags, self.start);
+		let Some(span) = found else {
+			self.start = self.haystack.len() + 1;
+			return None
+		};
+		// An empty span would be found again, so the next one must start later.
+		self.start = if span.is_empty() { span.end + 1 } else { span.end };
+		Some(span)
 	}
 }
 
+impl<T: PartialEq, P: Pieces<T>, M: Matcher<T>> core::iter::FusedIterator for FindIter<'_, '_, P, T, M> {}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PatternFlags(u8);
 
 impl PatternFlags {
 	const FLAG_START_UNANCHORED: u8 = 1 << 0;
 	const FLAG_END_ANCHORED: u8 = 1 << 1;
+	const FLAG_PATH_AWARE: u8 = 1 << 2;
+	const ALL_FLAGS: u8 = Self::FLAG_START_UNANCHORED | Self::FLAG_END_ANCHORED | Self::FLAG_PATH_AWARE;
 
 	#[inline]
 	pub const fn empty() -> Self {
 		Self(0)
 	}
 
+	/// Get the flags as bits, which are the same in every version of this crate.
+	#[inline]
+	pub const fn bits(self) -> u8 {
+		self.0
+	}
+
+	/// Get the flags from [`bits`](Self::bits), unless some of the bits aren't flags.
+	#[inline]
+	pub const fn from_bits(bits: u8) -> Option<Self> {
+		if bits & !Self::ALL_FLAGS == 0 {
+			Some(Self(bits))
+		} else {
+			None
+		}
+	}
+
 	#[inline]
 	pub const fn is_start_unanchored(self) -> bool {
 		(self.0 & Self::FLAG_START_UNANCHORED) != 0
@@ -83,24 +243,244 @@ impl PatternFlags {
 	pub const fn with_end_anchored(self) -> Self {
 		Self(self.0 | Self::FLAG_END_ANCHORED)
 	}
+
+	/// Returns `true` if [`Piece::Wildcard`], [`Piece::Any`] and [`Piece::Class`] don't match separators, as decided
+	/// by a [`Matcher`].
+	#[inline]
+	pub const fn is_path_aware(self) -> bool {
+		(self.0 & Self::FLAG_PATH_AWARE) != 0
+	}
+
+	#[inline]
+	#[must_use = "`with_path_aware` returns without modifying the original value"]
+	pub const fn with_path_aware(self) -> Self {
+		Self(self.0 | Self::FLAG_PATH_AWARE)
+	}
 }
 
+/// Sequence of [`Piece`]s that make up a pattern.
 pub trait Pieces<T> {
-	type Iter<'a>: Iterator<Item = &'a [T]> where Self: 'a, T: 'a;
+	type Iter<'a>: Iterator<Item = Piece<'a, T>> + Clone where Self: 'a, T: 'a;
 	fn pieces(&self) -> Self::Iter<'_>;
 }
 
-impl<T, P: Pieces<T>> Pieces<T> for &P {
+impl<T, P: ?Sized + Pieces<T>> Pieces<T> for &P {
 	type Iter<'a> = P::Iter<'a> where Self: 'a, T: 'a;
 	fn pieces(&self) -> Self::Iter<'_> {
 		Pieces::pieces(*self)
 	}
 }
 
+/// Literals, with a [`Piece::Wildcard`] between each of them.
+impl<T> Pieces<T> for [&[T]] {
+	type Iter<'a> = WildcardSeparated<'a, Copied<slice::Iter<'a, &'a [T]>>, T> where Self: 'a, T: 'a;
+	fn pieces(&self) -> Self::Iter<'_> {
+		WildcardSeparated::new(self.iter().copied())
+	}
+}
+
+/// Literals, with a [`Piece::Wildcard`] between each of them.
+impl<'b, T, const N: usize> Pieces<T> for [&'b [T]; N] {
+	type Iter<'a> = <[&'b [T]] as Pieces<T>>::Iter<'a> where Self: 'a, T: 'a;
+	fn pieces(&self) -> Self::Iter<'_> {
+		self.as_slice().pieces()
+	}
+}
+
+impl<T> Pieces<T> for [Piece<'_, T>] {
+	type Iter<'a> = Copied<slice::Iter<'a, Piece<'a, T>>> where Self: 'a, T: 'a;
+	fn pieces(&self) -> Self::Iter<'_> {
+		self.iter().copied()
+	}
+}
+
+impl<'b, T, const N: usize> Pieces<T> for [Piece<'b, T>; N] {
+	type Iter<'a> = <[Piece<'b, T>] as Pieces<T>>::Iter<'a> where Self: 'a, T: 'a;
+	fn pieces(&self) -> Self::Iter<'_> {
+		self.as_slice().pieces()
+	}
+}
+
+/// Part of a pattern that matches elements of a haystack.
+#[derive(PartialEq, Eq, Hash)]
+pub enum Piece<'a, T> {
+	/// Sequence of elements that must all be equal, as decided by a [`Matcher`].
+	Literal(&'a [T]),
+	/// Any sequence of elements, including an empty one.
+	///
+	/// In a path-aware pattern, the sequence can't contain separators.
+	Wildcard,
+	/// Any sequence of elements, including an empty one.
+	///
+	/// In a path-aware pattern, the sequence must be made of whole segments, each followed by a separator.
+	Globstar,
+	/// Any single element.
+	Any,
+	/// Any single element in (or not in) a [`Class`].
+	Class(Class<'a, T>),
+	/// Any one of the literals in an [`Alternation`].
+	Alternation(Alternation<'a, T>),
+}
+
+impl<T> Clone for Piece<'_, T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for Piece<'_, T> {}
+
+impl<T: fmt::Debug> fmt::Debug for Piece<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Literal(literal) => f.debug_tuple("Literal").field(literal).finish(),
+			Self::Wildcard => f.write_str("Wildcard"),
+			Self::Globstar => f.write_str("Globstar"),
+			Self::Any => f.write_str("Any"),
+			Self::Class(class) => f.debug_tuple("Class").field(class).finish(),
+			Self::Alternation(alternation) => f.debug_tuple("Alternation").field(alternation).finish(),
+		}
+	}
+}
+
+impl<'a, T> From<&'a [T]> for Piece<'a, T> {
+	#[inline]
+	fn from(value: &'a [T]) -> Self {
+		Self::Literal(value)
+	}
+}
+
+/// Set of elements that a [`Piece::Class`] accepts.
+///
+/// The set is stored as written in a pattern, so that it can be borrowed from the pattern: each element of `set` is
+/// a member, unless it's followed by the `range` element and another element, in which case all elements in that
+/// inclusive range are members.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Class<'a, T> {
+	pub set: &'a [T],
+	pub range: Option<&'a T>,
+	/// Whether the class accepts elements that *aren't* members of the set.
+	pub negated: bool,
+}
+
+impl<T> Clone for Class<'_, T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for Class<'_, T> {}
+
+impl<T: PartialEq> Class<'_, T> {
+	/// Returns `true` if the class accepts `t`, comparing elements with `matcher`.
+	///
+	/// `t` is the elements that the class matches as one, as split by [`Matcher::element_len`], which also splits
+	/// the set.
+	pub fn accepts<M: Matcher<T>>(&self, matcher: &M, t: &[T]) -> bool {
+		self.is_member(matcher, t) != self.negated
+	}
+
+	fn is_member<M: Matcher<T>>(&self, matcher: &M, t: &[T]) -> bool {
+		let mut set = self.set;
+		while !set.is_empty() {
+			let low;
+			(low, set) = set.split_at(matcher.element_len(set));
+			match (set.split_first(), self.range) {
+				(Some((marker, rest)), Some(range)) if marker == range && !rest.is_empty() => {
+					let high;
+					(high, set) = rest.split_at(matcher.element_len(rest));
+					if matcher.is_in_range(low, high, t) { return true }
+				}
+				_ => {
+					if matcher.is_equal(low, t) { return true }
+				}
+			}
+		}
+		false
+	}
+}
+
+/// Literals that a [`Piece::Alternation`] accepts any one of.
+///
+/// The alternatives are stored as written in a pattern, so that they can be borrowed from the pattern: they're
+/// separated by each `separator` element in `alternatives`, so without a separator, there's a single alternative.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Alternation<'a, T> {
+	pub alternatives: &'a [T],
+	pub separator: Option<&'a T>,
+}
+
+impl<T> Clone for Alternation<'_, T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for Alternation<'_, T> {}
+
+impl<'a, T: PartialEq> Alternation<'a, T> {
+	/// Iterate over the alternatives, in the order that they're written in.
+	pub fn alternatives(&self) -> impl Iterator<Item = &'a [T]> + Clone + use<'a, T> {
+		let separator = self.separator;
+		self.alternatives.split(move |t| separator == Some(t))
+	}
+}
+
+/// Iterator over literal [`Piece`]s, with a [`Piece::Wildcard`] between each of them.
+#[derive(Debug)]
+pub struct WildcardSeparated<'a, I, T> {
+	inner: I,
+	next_literal: Option<&'a [T]>,
+	has_started: bool,
+}
+
+impl<I: Clone, T> Clone for WildcardSeparated<'_, I, T> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			next_literal: self.next_literal,
+			has_started: self.has_started,
+		}
+	}
+}
+
+impl<'a, I: Iterator<Item = &'a [T]>, T> WildcardSeparated<'a, I, T> {
+	#[inline]
+	pub const fn new(inner: I) -> Self {
+		Self {
+			inner,
+			next_literal: None,
+			has_started: false,
+		}
+	}
+}
+
+impl<'a, I: Iterator<Item = &'a [T]>, T> Iterator for WildcardSeparated<'a, I, T> {
+	type Item = Piece<'a, T>;
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(literal) = self.next_literal.take() {
+			return Some(Piece::Literal(literal))
+		}
+		let literal = self.inner.next()?;
+		if self.has_started {
+			self.next_literal = Some(literal);
+			Some(Piece::Wildcard)
+		} else {
+			self.has_started = true;
+			Some(Piece::Literal(literal))
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use crate::*;
 	use u8_buf::*;
+	#[cfg(feature = "alloc")]
+	use alloc::{
+		string::ToString,
+		vec, vec::Vec,
+	};
 
 	const WILDCARD: &u8 = &b'*';
 
@@ -137,4 +517,333 @@ mod tests {
 		let pattern: Pattern<U8Pieces, u8> = Pattern::parse(b"*.nav*", WILDCARD);
 		assert_eq!(pattern.first_match(PathMatch, b"cp_dustbowl.nav  "), Some(b"  ".as_ref()));
 	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_generic_pieces() {
+		let wide = move |s: &str| s.encode_utf16().collect::<Vec<_>>();
+		let (pattern, haystack) = (wide("maps/*/cp_*.bsp"), wide("maps/workshop/cp_well.bsp"));
+		let pattern = Pattern::<vec_buf::VecPieces<u16>, u16>::parse(&pattern, &u16::from(b'*'));
+		assert!(pattern.first_match(ExactMatch, &haystack).is_some());
+		assert!(pattern.first_match(ExactMatch, &wide("maps/workshop/koth_well.bsp")).is_none());
+
+		let pattern: Vec<_> = "[!a-z]*ß".chars().collect();
+		let pattern = Pattern::<vec_buf::VecPieces<char>, char>::parse_with(&pattern, &Syntax::<char>::GLOB);
+		assert!(pattern.first_match(OrdMatch, &['S', 't', 'r', 'a', 'ß']).is_some());
+		assert!(pattern.first_match(OrdMatch, &['s', 't', 'r', 'a', 'ß']).is_none());
+		// Without an order, only the ends of a range are in it.
+		assert!(pattern.first_match(ExactMatch, &['s', 't', 'r', 'a', 'ß']).is_some());
+		assert!(pattern.first_match(ExactMatch, &['a', 't', 'r', 'a', 'ß']).is_none());
+
+		let pattern = Pattern::new([b"cp_".as_ref(), b".bsp"], PatternFlags::empty().with_end_anchored());
+		assert!(pattern.first_match(ExactMatch, b"cp_well.bsp").is_some());
+		let pieces: &[&[u8]] = &[b"cp_", b".bsp"];
+		assert!(Pattern::new(pieces, PatternFlags::empty()).first_match(ExactMatch, b"cp_well.bsp.bz2").is_some());
+		let pattern = Pattern::new([Piece::Any, Piece::Literal(b"p_")], PatternFlags::empty().with_start_unanchored());
+		assert!(pattern.first_match(ExactMatch, b"cp_well.bsp").is_some());
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_end_anchored() {
+		let first_match = move |pattern: &[u8], haystack: &'static [u8]| {
+			Pattern::<U8Pieces, u8>::parse(pattern, WILDCARD).first_match(ExactMatch, haystack)
+		};
+		assert_eq!(first_match(b"*.nav", b"a.nav.nav"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"*.nav", b"a.nav.bsp"), None);
+		assert_eq!(first_match(b"*a", b"aba"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"*a", b"ab"), None);
+		assert_eq!(first_match(b"a*a", b"aa"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"a*a", b"a"), None);
+		assert_eq!(first_match(b"ab*ba", b"aba"), None);
+		assert_eq!(first_match(b"ab*ba", b"abba"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"*a*b", b"abab"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"a*b*c", b"abcbc"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"a*b*c", b"abcbd"), None);
+		assert_eq!(first_match(b"maps/*.bsp", b"maps/a.bsp/b.bsp"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"abc", b"abc"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"abc", b"abcabc"), None);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_end_unanchored() {
+		let first_match = move |pattern: &[u8], haystack: &'static [u8]| {
+			Pattern::<U8Pieces, u8>::parse(pattern, WILDCARD).first_match(ExactMatch, haystack)
+		};
+		assert_eq!(first_match(b"a*", b"abc"), Some(b"bc".as_ref()));
+		assert_eq!(first_match(b"*x*", b"yxyxy"), Some(b"yxy".as_ref()));
+		assert_eq!(first_match(b"*a*b*", b"bab"), Some(b"".as_ref()));
+		assert_eq!(first_match(b"*a*b*", b"bba"), None);
+		assert_eq!(first_match(b"*", b"anything"), Some(b"anything".as_ref()));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_parses_glob() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::GLOB);
+		let new = move |pieces: Vec<Piece<'static, u8>>, flags| Pattern::new(pieces.into_iter().collect(), flags);
+		assert_eq!(parse(b"cp_*_b?.bsp"), new(vec![
+			Piece::Literal(b"cp_"),
+			Piece::Wildcard,
+			Piece::Literal(b"_b"),
+			Piece::Any,
+			Piece::Literal(b".bsp"),
+		], PatternFlags::empty().with_end_anchored()));
+		assert_eq!(parse(b"[!a-z]**"), new(vec![
+			Piece::Class(Class { set: b"a-z", range: Some(&b'-'), negated: true }),
+		], PatternFlags::empty()));
+		assert_eq!(parse(b"a**b"), new(vec![
+			Piece::Literal(b"a"),
+			Piece::Wildcard,
+			Piece::Literal(b"b"),
+		], PatternFlags::empty().with_end_anchored()));
+		assert_eq!(parse(b"[]][a"), new(vec![
+			Piece::Class(Class { set: b"]", range: None, negated: false }),
+			Piece::Literal(b"[a"),
+		], PatternFlags::empty().with_end_anchored()));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_finds_all() {
+		let find_all = move |pattern: &[u8], syntax, haystack: &[u8]| {
+			let pattern = Pattern::<U8Pieces, u8>::parse_with(pattern, syntax);
+			pattern.find_iter(PathMatch, haystack).map(move |span| (span.start, span.end)).collect::<Vec<_>>()
+		};
+		let vmt = b"\"$basetexture\" \"Maps/A.vtf\"\n\"$bumpmap\" \"maps/b.vtf\"";
+		assert_eq!(find_all(b"maps/*.vtf", &Syntax::<u8>::GLOB, vmt), [(16, 26), (40, 50)]);
+		assert_eq!(find_all(b"*.vtf*", &Syntax::<u8>::GLOB, vmt), [(22, 26), (46, 50)]);
+		assert_eq!(find_all(b"maps/*", &Syntax::<u8>::PATH_GLOB, b"maps/a/b"), [(0, 5)]);
+		assert_eq!(find_all(b"a?", &Syntax::<u8>::PATH_GLOB, b"a/aaab"), [(2, 4), (4, 6)]);
+		assert_eq!(find_all(b"x", &Syntax::<u8>::GLOB, b"aaa"), []);
+		assert_eq!(find_all(b"", &Syntax::<u8>::GLOB, b"ab"), [(0, 0), (1, 1), (2, 2)]);
+		assert_eq!(find_all(b"[0-9]*", &Syntax::<u8>::GLOB, b"a1b22"), [(1, 2), (3, 4), (4, 5)]);
+
+		// Spans may start after the positions where a leading globstar ran out of ones to try.
+		let find = move |pattern: &[u8], haystack: &[u8]| {
+			Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB).find(PathMatch, haystack)
+		};
+		assert_eq!(find(b"**/a**/", b"*ba/a"), Some(2..4));
+		assert_eq!(find(b"**/.*/**", b"a*./."), Some(2..4));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_parses_escapes() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::GLOB);
+		let new = move |pieces: Vec<Piece<'static, u8>>, flags| Pattern::new(pieces.into_iter().collect(), flags);
+		assert_eq!(parse(b"a\\*b"), new(vec![
+			Piece::Literal(b"a"),
+			Piece::Literal(b"*"),
+			Piece::Literal(b"b"),
+		], PatternFlags::empty().with_end_anchored()));
+		assert_eq!(parse(b"*\\*"), new(vec![
+			Piece::Literal(b"*"),
+		], PatternFlags::empty().with_start_unanchored().with_end_anchored()));
+		assert_eq!(parse(b"\\\\*"), new(vec![
+			Piece::Literal(b"\\"),
+		], PatternFlags::empty()));
+		assert_eq!(parse(b"\\[a]\\"), new(vec![
+			Piece::Literal(b"["),
+			Piece::Literal(b"a]"),
+			Piece::Literal(b"\\"),
+		], PatternFlags::empty().with_end_anchored()));
+
+		let exact = move |pattern: &'static [u8], haystack: &[u8]| parse(pattern).first_match(ExactMatch, haystack).is_some();
+		assert!(exact(b"\\#notes\\ ", b"#notes "));
+		assert!(!exact(b"\\#notes\\ ", b"#notes"));
+		assert!(exact(b"what\\?", b"what?"));
+		assert!(!exact(b"what\\?", b"whats"));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_displays() {
+		for (pattern, syntax) in [
+			(b"cp_*_b?.bsp".as_ref(), &Syntax::<u8>::GLOB),
+			(b"*[!a-z]\\*b\\\\*", &Syntax::<u8>::GLOB),
+			(b"", &Syntax::<u8>::GLOB),
+			(b"maps/**/*.bsp", &Syntax::<u8>::PATH_GLOB),
+			(b"**/sound/**", &Syntax::<u8>::PATH_GLOB),
+			(b"\\#notes\\ ", &Syntax::<u8>::PATH_GLOB),
+			(b"maps/*.{bsp,nav,}\\{", &Syntax::<u8>::PATH_GLOB),
+		] {
+			// Escaped elements are parsed into literals of their own, so round trips are compared as text.
+			let displayed = Pattern::<U8Pieces, u8>::parse_with(pattern, syntax).to_string();
+			let reparsed = Pattern::<U8Pieces, u8>::parse_with(displayed.as_bytes(), syntax);
+			assert_eq!(reparsed.to_string(), displayed);
+		}
+		let display = move |pattern, syntax| Pattern::<U8Pieces, u8>::parse_with(pattern, syntax).to_string();
+		assert_eq!(display(b"maps/**/*.bsp", &Syntax::<u8>::PATH_GLOB), "maps/**/*.bsp");
+		assert_eq!(display(b"**a\\*[!0-9]*", &Syntax::<u8>::GLOB), "*a\\*[!0-9]*");
+		assert_eq!(display(b"\\#notes\\ ", &Syntax::<u8>::GLOB), "#notes ");
+		assert_eq!(display(b"\\{a,b}", &Syntax::<u8>::GLOB), "\\{a,b}");
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_prefixes() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+		let prefix_matches = move |pattern, prefix: &[u8]| parse(pattern).is_prefix_match(PathMatch, prefix);
+		assert!(prefix_matches(b"sound/custom/intro.wav", b"sound/"));
+		assert!(prefix_matches(b"sound/custom/intro.wav", b"Sound/Custom/"));
+		assert!(!prefix_matches(b"sound/custom/intro.wav", b"sound/music/"));
+		assert!(!prefix_matches(b"sound/custom/intro.wav", b"sound/custom/intro.wav/"));
+		assert!(prefix_matches(b"**/*.wav", b"sound/music/"));
+		assert!(prefix_matches(b"*/custom/*.wav", b"sound/"));
+		assert!(prefix_matches(b"*/custom/*.wav", b"sound/cus"));
+		assert!(!prefix_matches(b"*/custom/*.wav", b"sound/music/"));
+		assert!(prefix_matches(b"sound/{vo,music}/*.wav", b"sound/mu"));
+		assert!(!prefix_matches(b"sound/{vo,music}/*.wav", b"sound/custom/"));
+		assert!(!prefix_matches(b"maps/*.bsp", b"sound/"));
+		assert!(prefix_matches(b"", b""));
+		assert!(!prefix_matches(b"", b"a"));
+
+		let glob = Pattern::<U8Pieces, u8>::parse_with(b"*.wav", &Syntax::<u8>::GLOB);
+		assert!(glob.is_prefix_match(ExactMatch, b"sound/"));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_alternations() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+		assert_eq!(parse(b"*.{vmt,vtf}").pieces.pieces().collect::<Vec<_>>(), [
+			Piece::Wildcard,
+			Piece::Literal(b"."),
+			Piece::Alternation(Alternation { alternatives: b"vmt,vtf", separator: Some(&b',') }),
+		]);
+		assert_eq!(parse(b"{a").pieces.pieces().collect::<Vec<_>>(), [Piece::Literal(b"{a")]);
+
+		let matches = move |pattern, haystack: &[u8]| parse(pattern).first_match(PathMatch, haystack).is_some();
+		assert!(matches(b"materials/*.{vmt,vtf}", b"materials/wall.vtf"));
+		assert!(matches(b"materials/*.{vmt,vtf}", b"Materials/Wall.VMT"));
+		assert!(!matches(b"materials/*.{vmt,vtf}", b"materials/wall.vtx"));
+		assert!(!matches(b"materials/*.{vmt,vtf}", b"materials/dev/wall.vtf"));
+		// Later alternatives are tried if the rest of the pattern doesn't match after earlier ones.
+		assert!(matches(b"maps/{cp,cp_}*well.bsp", b"maps/cp_well.bsp"));
+		assert!(matches(b"maps/{a,ab}c", b"maps/abc"));
+		assert!(matches(b"maps/*.{bsp,nav,}", b"maps/cp_well."));
+		assert!(!matches(b"maps/*.{bsp,nav,}", b"maps/cp_well"));
+		assert!(matches(b"{maps/,}*.nav", b"cp_well.nav"));
+		assert!(matches(b"{maps/,}*.nav", b"maps/cp_well.nav"));
+		// Alternatives are literal.
+		assert!(!matches(b"{maps/*/,}*.nav", b"maps/workshop/cp_well.nav"));
+		assert!(matches(b"{maps/*/,}*.nav", b"maps/*/cp_well.nav"));
+		assert!(matches(b"maps/{}x", b"maps/x"));
+
+		let pattern = parse(b"sound/{vo,music}/*.{wav,mp3}");
+		assert_eq!(pattern.captures(PathMatch, b"sound/music/theme.mp3"), Some(vec![6..11, 12..17, 18..21]));
+		assert_eq!(pattern.find(PathMatch, b"x sound/vo/a.wav y"), Some(2..16));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_glob() {
+		let exact = move |pattern: &[u8], haystack: &[u8]| {
+			Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::GLOB).first_match(ByteMatch, haystack).is_some()
+		};
+		let path = move |pattern: &[u8], haystack: &[u8]| {
+			Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::GLOB).first_match(PathMatch, haystack).is_some()
+		};
+		assert!(exact(b"cp_*_b?.bsp", b"cp_badlands_b4.bsp"));
+		assert!(!exact(b"cp_*_b?.bsp", b"cp_badlands_b12.bsp"));
+		assert!(!exact(b"cp_*_b?.bsp", b"cp_badlands_rc1.bsp"));
+		assert!(!exact(b"?", b""));
+		assert!(exact(b"*?", b"ab"));
+		assert!(exact(b"[a-z]*", b"dustbowl"));
+		assert!(!exact(b"[a-z]*", b"Dustbowl"));
+		assert!(exact(b"[!a-z]*", b"Dustbowl"));
+		assert!(!exact(b"[!a-z]*", b"dustbowl"));
+		assert!(exact(b"*_[0-9][0-9a-]", b"pl_a_1a"));
+		assert!(exact(b"*_[0-9][0-9a-]", b"pl_a_1-"));
+		assert!(!exact(b"*_[0-9][0-9a-]", b"pl_a_1b"));
+		assert!(exact(b"[abc", b"[abc"));
+		assert!(path(b"[a-z]*", b"Dustbowl"));
+		assert!(path(b"[A-Z]*", b"dustbowl"));
+		assert!(path(b"maps[/]*", b"maps\\x.bsp"));
+		assert!(!path(b"maps[!/]*", b"maps\\x.bsp"));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_parses_path_glob() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+		let new = move |pieces: Vec<Piece<'static, u8>>| {
+			Pattern::new(pieces.into_iter().collect(), PatternFlags::empty().with_path_aware().with_end_anchored())
+		};
+		assert_eq!(parse(b"maps/*.bsp"), new(vec![Piece::Literal(b"maps/"), Piece::Wildcard, Piece::Literal(b".bsp")]));
+		assert_eq!(parse(b"**/a/**/b"), new(vec![
+			Piece::Globstar,
+			Piece::Literal(b"a/"),
+			Piece::Globstar,
+			Piece::Literal(b"b"),
+		]));
+		assert_eq!(parse(b"a/**"), new(vec![Piece::Literal(b"a/"), Piece::Globstar, Piece::Wildcard]));
+		assert_eq!(parse(b"a**/b"), new(vec![Piece::Literal(b"a"), Piece::Wildcard, Piece::Literal(b"/b")]));
+		assert_eq!(parse(b"a/**b"), new(vec![Piece::Literal(b"a/"), Piece::Wildcard, Piece::Literal(b"b")]));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_matches_path_glob() {
+		let path = move |pattern: &[u8], haystack: &[u8]| {
+			Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB).first_match(PathMatch, haystack).is_some()
+		};
+		assert!(path(b"maps/*.bsp", b"maps/cp_dustbowl.bsp"));
+		assert!(path(b"maps/*.bsp", b"Maps\\cp_dustbowl.BSP"));
+		assert!(!path(b"maps/*.bsp", b"maps/workshop/sub/x.bsp"));
+		assert!(!path(b"maps/*.bsp", b"maps/a.bsp/b.bsp"));
+		assert!(path(b"maps/**/*.bsp", b"maps/x.bsp"));
+		assert!(path(b"maps/**/*.bsp", b"maps/workshop/sub/x.bsp"));
+		assert!(path(b"maps/**/*.bsp", b"maps\\workshop\\x.bsp"));
+		assert!(!path(b"maps/**/*.bsp", b"mapsx.bsp"));
+		assert!(path(b"**/*.nav", b"maps/a.nav"));
+		assert!(path(b"**/*.nav", b"a.nav"));
+		assert!(!path(b"**/*.nav", b"maps/a.nav/b"));
+		assert!(path(b"sound/**", b"sound/a/b.wav"));
+		assert!(!path(b"sound/**", b"sounds/a.wav"));
+		assert!(path(b"**", b"a/b/c"));
+		assert!(!path(b"m?ps/*", b"m/ps/a"));
+		assert!(!path(b"m[!a]ps/*", b"m/ps/a"));
+		assert!(path(b"*/*/c", b"a/b/c"));
+		assert!(!path(b"*/c", b"a/b/c"));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_captures() {
+		let captures = move |pattern: &[u8], syntax: &Syntax<u8>, haystack: &[u8]| {
+			let captures = Pattern::<U8Pieces, u8>::parse_with(pattern, syntax).captures(PathMatch, haystack)?;
+			Some(captures.into_iter().map(move |capture| (capture.start, capture.end)).collect::<Vec<_>>())
+		};
+		assert_eq!(captures(b"maps/*.bsp", &Syntax::<u8>::GLOB, b"maps/a.bsp"), Some(vec![(5, 6)]));
+		assert_eq!(captures(b"*.bsp", &Syntax::<u8>::GLOB, b"maps/a.bsp"), Some(vec![(0, 6)]));
+		assert_eq!(captures(b"maps/*", &Syntax::<u8>::GLOB, b"maps/a.bsp"), Some(vec![(5, 10)]));
+		assert_eq!(captures(b"*_b?.*", &Syntax::<u8>::GLOB, b"cp_a_b4.bsp"), Some(vec![(0, 4), (6, 7), (8, 11)]));
+		assert_eq!(captures(b"*a**b*", &Syntax::<u8>::GLOB, b"xaab"), Some(vec![(0, 1), (2, 3), (4, 4)]));
+		assert_eq!(captures(b"maps/**/*.bsp", &Syntax::<u8>::PATH_GLOB, b"maps/x.bsp"), Some(vec![(5, 5), (5, 6)]));
+		assert_eq!(
+			captures(b"maps/**/*.bsp", &Syntax::<u8>::PATH_GLOB, b"maps/a/b/x.bsp"),
+			Some(vec![(5, 9), (9, 10)]),
+		);
+		assert_eq!(captures(b"maps/*.bsp", &Syntax::<u8>::GLOB, b"maps/a.nav"), None);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn pattern_rewrites() {
+		let rewrite = move |pattern: &[u8], template: &[u8], haystack: &[u8]| {
+			let pattern = Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+			let template = Pattern::<U8Pieces, u8>::parse_with(template, &Syntax::<u8>::PATH_GLOB);
+			pattern.rewrite(PathMatch, haystack, &template)
+		};
+		assert_eq!(rewrite(b"maps/*.bsp", b"maps/archive/*.bsp", b"maps/a.bsp"), Some(b"maps/archive/a.bsp".to_vec()));
+		assert_eq!(rewrite(b"maps/*.bsp", b"maps/archive/*.bsp", b"maps/a/b.bsp"), None);
+		assert_eq!(
+			rewrite(b"**/*_b?.bsp", b"**/old/*_b?.bsp", b"maps\\x/cp_a_b4.bsp"),
+			Some(b"maps\\x/old/cp_a_b4.bsp".to_vec()),
+		);
+		assert_eq!(rewrite(b"*/*", b"*", b"a/b"), Some(b"a".to_vec()));
+	}
 }
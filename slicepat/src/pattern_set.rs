@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+
+use crate::{
+	KeyMatcher, Piece, Pieces, RuleSet,
+};
+
+/// [`RuleSet`] compiled for matching a haystack against all of its patterns at once.
+///
+/// The longest literal of each pattern is searched for in a single pass over the haystack, and only the patterns
+/// whose literals were found, along with those without literals, are then matched against the haystack.
+#[derive(Debug, Clone)]
+pub struct PatternSet<P, T, V, M: KeyMatcher<T>> {
+	rules: RuleSet<P, T, V>,
+	matcher: M,
+	automaton: Automaton<M::Key>,
+	/// Indices of the rules whose patterns have no literals, in ascending order.
+	unfiltered: Vec<usize>,
+}
+
+impl<P: Pieces<T>, T: PartialEq, V, M: KeyMatcher<T>> PatternSet<P, T, V, M> {
+	/// Compile `rules`, whose patterns are to be matched with `matcher`.
+	pub fn new(rules: RuleSet<P, T, V>, matcher: M) -> Self {
+		let mut automaton = Automaton::new();
+		let mut unfiltered = Vec::new();
+		for (index, (pattern, _)) in rules.rules().iter().enumerate() {
+			let longest_literal = pattern.pieces.pieces()
+				.filter_map(move |piece| match piece {
+					Piece::Literal(literal) => Some(literal),
+					_ => None,
+				})
+				.max_by_key(move |literal| literal.len())
+				.filter(move |literal| !literal.is_empty());
+			match longest_literal {
+				Some(literal) => automaton.insert(literal.iter().map(|t| matcher.key(t)), index),
+				None => unfiltered.push(index),
+			}
+		}
+		automaton.link();
+
+		Self {
+			rules,
+			matcher,
+			automaton,
+			unfiltered,
+		}
+	}
+
+	/// Get the rules that the set was compiled from.
+	#[inline]
+	pub fn rules(&self) -> &RuleSet<P, T, V> {
+		&self.rules
+	}
+
+	#[inline]
+	pub fn into_rules(self) -> RuleSet<P, T, V> {
+		self.rules
+	}
+
+	/// Get the indices of the rules whose patterns match `haystack`, in ascending order.
+	pub fn matches(&self, haystack: &[T]) -> Vec<usize> {
+		let mut candidates = self.candidates(haystack);
+		candidates.retain(|&index| self.is_match_at(index, haystack));
+		candidates
+	}
+
+	/// Get the index of the last rule whose pattern matches `haystack`.
+	pub fn last_match_index(&self, haystack: &[T]) -> Option<usize> {
+		self.candidates(haystack).into_iter()
+			.rev()
+			.find(|&index| self.is_match_at(index, haystack))
+	}
+
+	/// Get the value of the last rule whose pattern matches `haystack`, like [`RuleSet::last_match`].
+	pub fn last_match(&self, haystack: &[T]) -> Option<&V> {
+		self.last_match_index(haystack).map(move |index| &self.rules.rules()[index].1)
+	}
+
+	/// Get the indices of the rules whose patterns may match `haystack`, in ascending order.
+	fn candidates(&self, haystack: &[T]) -> Vec<usize> {
+		let mut candidates = self.unfiltered.clone();
+		self.automaton.find_all(haystack.iter().map(|t| self.matcher.key(t)), &mut candidates);
+		candidates.sort_unstable();
+		candidates.dedup();
+		candidates
+	}
+
+	fn is_match_at(&self, index: usize, haystack: &[T]) -> bool {
+		self.rules.rules()[index].0.first_match(&self.matcher, haystack).is_some()
+	}
+}
+
+impl<P, T, V, M: KeyMatcher<T> + Default> Default for PatternSet<P, T, V, M> {
+	fn default() -> Self {
+		Self {
+			rules: RuleSet::new(),
+			matcher: M::default(),
+			automaton: Automaton::new(),
+			unfiltered: Vec::new(),
+		}
+	}
+}
+
+/// Aho-Corasick automaton, finding all occurrences of many sequences of keys in a single pass.
+#[derive(Debug, Clone)]
+struct Automaton<K> {
+	nodes: Vec<Node<K>>,
+}
+
+#[derive(Debug, Clone)]
+struct Node<K> {
+	/// Transitions to other nodes, sorted by key.
+	transitions: Vec<(K, usize)>,
+	/// Node for the longest proper suffix of this node's sequence that's also a prefix of an inserted sequence.
+	fail: usize,
+	/// Values of the inserted sequences that end at this node, including those that are suffixes of others.
+	outputs: Vec<usize>,
+}
+
+impl<K> Node<K> {
+	const fn new() -> Self {
+		Self {
+			transitions: Vec::new(),
+			fail: 0,
+			outputs: Vec::new(),
+		}
+	}
+}
+
+impl<K: Ord + Clone> Automaton<K> {
+	fn new() -> Self {
+		Self {
+			nodes: alloc::vec![Node::new()],
+		}
+	}
+
+	fn transition(&self, node: usize, key: &K) -> Option<usize> {
+		let transitions = &self.nodes[node].transitions;
+		transitions.binary_search_by(move |(k, _)| k.cmp(key)).ok().map(move |i| transitions[i].1)
+	}
+
+	/// Insert the sequence `keys`, to be reported with `value`.
+	///
+	/// [`link`](Self::link) must be called after inserting sequences, and before finding them.
+	fn insert<I: IntoIterator<Item = K>>(&mut self, keys: I, value: usize) {
+		let mut node = 0;
+		for key in keys {
+			node = match self.nodes[node].transitions.binary_search_by(|(k, _)| k.cmp(&key)) {
+				Ok(i) => self.nodes[node].transitions[i].1,
+				Err(i) => {
+					let next = self.nodes.len();
+					self.nodes.push(Node::new());
+					self.nodes[node].transitions.insert(i, (key, next));
+					next
+				}
+			};
+		}
+		self.nodes[node].outputs.push(value);
+	}
+
+	/// Compute the failure links of all nodes, in breadth-first order.
+	fn link(&mut self) {
+		// Children of the root fail to the root, which `Node::new` already links them to.
+		let mut queue: Vec<usize> = self.nodes[0].transitions.iter().map(move |&(_, child)| child).collect();
+		let mut next_in_queue = 0;
+		while let Some(&node) = queue.get(next_in_queue) {
+			next_in_queue += 1;
+			for i in 0..self.nodes[node].transitions.len() {
+				let (key, child) = self.nodes[node].transitions[i].clone();
+				let mut fail = self.nodes[node].fail;
+				let fail = loop {
+					if let Some(next) = self.transition(fail, &key) {
+						break next
+					}
+					if fail == 0 {
+						break 0
+					}
+					fail = self.nodes[fail].fail;
+				};
+				self.nodes[child].fail = fail;
+				let inherited = self.nodes[fail].outputs.clone();
+				self.nodes[child].outputs.extend(inherited);
+				queue.push(child);
+			}
+		}
+	}
+
+	/// Push the values of all sequences that occur in `keys` onto `found`.
+	fn find_all<I: IntoIterator<Item = K>>(&self, keys: I, found: &mut Vec<usize>) {
+		let mut node = 0;
+		for key in keys {
+			node = loop {
+				if let Some(next) = self.transition(node, &key) {
+					break next
+				}
+				if node == 0 {
+					break 0
+				}
+				node = self.nodes[node].fail;
+			};
+			found.extend_from_slice(&self.nodes[node].outputs);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+	use u8_buf::*;
+	use alloc::vec::Vec;
+
+	#[test]
+	fn pattern_set_matches_like_rule_set() {
+		let rules: RuleSet<U8Pieces, u8, usize> = [
+			b"maps/*.bsp".as_ref(), b"**/*.nav", b"maps/**", b"**/cp_*", b"*", b"**/[a-z]?", b"sound/**/*.wav",
+			b"**/shop/**", b"**/workshop/*", b"**/op/**",
+		].into_iter()
+			.enumerate()
+			.map(move |(index, pattern)| (Pattern::parse_with(pattern, &Syntax::<u8>::PATH_GLOB), index))
+			.collect();
+		let haystacks = [
+			b"maps/cp_dustbowl.bsp".as_ref(), b"MAPS\\CP_DUSTBOWL.NAV", b"maps/workshop/a.bsp", b"sound/a/b.wav",
+			b"ab", b"x/yz", b"x/y/z.txt", b"", b"workshop/shop/op/a",
+		];
+		let set = PatternSet::new(rules.clone(), PathMatch);
+		for haystack in haystacks {
+			let expected: Vec<usize> = rules.rules().iter()
+				.enumerate()
+				.filter(|(_, (pattern, _))| pattern.first_match(PathMatch, haystack).is_some())
+				.map(move |(index, _)| index)
+				.collect();
+			assert_eq!(set.matches(haystack), expected);
+			assert_eq!(set.last_match(haystack), rules.last_match(PathMatch, haystack));
+		}
+		assert_eq!(set.matches(b"maps/cp_dustbowl.bsp"), [0, 2, 3]);
+	}
+}
@@ -0,0 +1,104 @@
+use alloc::vec::Vec;
+
+use crate::{
+	Matcher, Pattern, Pieces,
+};
+
+/// Ordered list of rules, each a pattern with a value, in which later rules override earlier ones.
+///
+/// Unlike a map from patterns to values, a rule set keeps every rule, including those with equal patterns.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RuleSet<P, T, V> {
+	rules: Vec<(Pattern<P, T>, V)>,
+}
+
+impl<P, T, V> RuleSet<P, T, V> {
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			rules: Vec::new(),
+		}
+	}
+
+	#[inline]
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self {
+			rules: Vec::with_capacity(capacity),
+		}
+	}
+
+	/// Add a rule, which overrides the rules already in the set.
+	#[inline]
+	pub fn push(&mut self, pattern: Pattern<P, T>, value: V) {
+		self.rules.push((pattern, value));
+	}
+
+	/// Get the rules in the set, from the first to the last one.
+	#[inline]
+	pub fn rules(&self) -> &[(Pattern<P, T>, V)] {
+		&self.rules
+	}
+
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.rules.len()
+	}
+
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.rules.is_empty()
+	}
+}
+
+impl<P: Pieces<T>, T: PartialEq, V> RuleSet<P, T, V> {
+	/// Get the value of the last rule whose pattern matches `haystack`.
+	pub fn last_match<M: Matcher<T>>(&self, matcher: M, haystack: &[T]) -> Option<&V> {
+		self.rules.iter()
+			.rev()
+			.find(|(pattern, _)| pattern.first_match(&matcher, haystack).is_some())
+			.map(move |(_, value)| value)
+	}
+}
+
+impl<P, T, V> Default for RuleSet<P, T, V> {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<P, T, V> FromIterator<(Pattern<P, T>, V)> for RuleSet<P, T, V> {
+	fn from_iter<I: IntoIterator<Item = (Pattern<P, T>, V)>>(iter: I) -> Self {
+		Self {
+			rules: iter.into_iter().collect(),
+		}
+	}
+}
+
+impl<P, T, V> Extend<(Pattern<P, T>, V)> for RuleSet<P, T, V> {
+	fn extend<I: IntoIterator<Item = (Pattern<P, T>, V)>>(&mut self, iter: I) {
+		self.rules.extend(iter);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+	use u8_buf::*;
+
+	#[test]
+	fn last_rule_wins() {
+		let parse = move |pattern: &'static [u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+		let mut rules = RuleSet::new();
+		rules.push(parse(b"maps/*"), true);
+		rules.push(parse(b"maps/*.nav"), false);
+		rules.push(parse(b"maps/keep_*.nav"), true);
+		rules.push(parse(b"maps/*"), false);
+		rules.push(parse(b"maps/*.bsp"), true);
+		assert_eq!(rules.len(), 5);
+		assert_eq!(rules.last_match(PathMatch, b"maps/a.bsp"), Some(&true));
+		assert_eq!(rules.last_match(PathMatch, b"maps/keep_a.nav"), Some(&false));
+		assert_eq!(rules.last_match(PathMatch, b"maps/a.txt"), Some(&false));
+		assert_eq!(rules.last_match(PathMatch, b"sound/a.wav"), None);
+	}
+}
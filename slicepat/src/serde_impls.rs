@@ -0,0 +1,278 @@
+//! Implementations of [`serde`] traits, behind the `serde` feature.
+//!
+//! Patterns are serialized as their flags and pieces, [`U8Pieces`] as its portable encoding, and [`VecPieces`] as a
+//! sequence of its pieces.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::fmt;
+use core::marker::PhantomData;
+
+#[cfg(feature = "alloc")]
+use serde::de::{
+	SeqAccess, Visitor,
+};
+use serde::{
+	de::{self, Deserialize, Deserializer},
+	ser::{Serialize, Serializer},
+};
+
+#[cfg(feature = "alloc")]
+use crate::{
+	Alternation, Class, KeyMatcher, PatternSet, Piece, Pieces, RuleSet,
+	u8_buf::{
+		U8Pieces,
+		TAG_ALTERNATION, TAG_ANY, TAG_CLASS, TAG_GLOBSTAR, TAG_LITERAL, TAG_WILDCARD,
+	},
+	vec_buf::VecPieces,
+};
+use crate::{
+	Pattern, PatternFlags,
+};
+
+impl Serialize for PatternFlags {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_u8(self.bits())
+	}
+}
+
+impl<'de> Deserialize<'de> for PatternFlags {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let bits = u8::deserialize(deserializer)?;
+		Self::from_bits(bits)
+			.ok_or_else(move || de::Error::invalid_value(de::Unexpected::Unsigned(bits.into()), &"pattern flags"))
+	}
+}
+
+impl<P: Serialize, T> Serialize for Pattern<P, T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		(self.flags, &self.pieces).serialize(serializer)
+	}
+}
+
+impl<'de, P: Deserialize<'de>, T> Deserialize<'de> for Pattern<P, T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let (flags, pieces) = Deserialize::deserialize(deserializer)?;
+		Ok(Self { flags, pieces, piece_t: PhantomData })
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl Serialize for U8Pieces {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(self.as_bytes())
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'de> Deserialize<'de> for U8Pieces {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct BytesVisitor;
+		impl<'de> Visitor<'de> for BytesVisitor {
+			type Value = U8Pieces;
+			fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+				f.write_str("encoded pattern pieces")
+			}
+			fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+				self.visit_byte_buf(v.to_vec())
+			}
+			fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+				U8Pieces::from_bytes(v).map_err(E::custom)
+			}
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+				let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+				while let Some(byte) = seq.next_element()? {
+					bytes.push(byte);
+				}
+				self.visit_byte_buf(bytes)
+			}
+		}
+		deserializer.deserialize_byte_buf(BytesVisitor)
+	}
+}
+
+/// Set on the tag of a negated class, in the encoding of [`VecPieces`].
+#[cfg(feature = "alloc")]
+const FLAG_NEGATED: u8 = 0x10;
+/// Set on the tag of a class with a range element, or an alternation with a separator, which follows its other
+/// elements, in the encoding of [`VecPieces`].
+#[cfg(feature = "alloc")]
+const FLAG_EXTRA: u8 = 0x20;
+
+/// Serialized as a sequence of pieces, each a tag and its elements.
+///
+/// Tags are the kinds of pieces, as in the encoding of [`U8Pieces`], which keeps the flags of a piece in a separate
+/// byte, whereas here they're set on the tag itself: `0x10` for a negated class, and `0x20` for a class with a range
+/// element or an alternation with a separator, which is the last of its elements.
+#[cfg(feature = "alloc")]
+impl<T: Serialize> Serialize for VecPieces<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_seq(self.pieces().map(SerializePiece))
+	}
+}
+
+#[cfg(feature = "alloc")]
+struct SerializePiece<'a, T>(Piece<'a, T>);
+
+#[cfg(feature = "alloc")]
+impl<T: Serialize> Serialize for SerializePiece<'_, T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		struct Elements<'a, T>(&'a [T], Option<&'a T>);
+		impl<T: Serialize> Serialize for Elements<'_, T> {
+			fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+				serializer.collect_seq(self.0.iter().chain(self.1))
+			}
+		}
+
+		let extra_flag = move |extra: Option<&T>| if extra.is_some() { FLAG_EXTRA } else { 0 };
+		let (tag, elements) = match self.0 {
+			Piece::Literal(literal) => (TAG_LITERAL, Elements(literal, None)),
+			Piece::Wildcard => (TAG_WILDCARD, Elements(&[], None)),
+			Piece::Any => (TAG_ANY, Elements(&[], None)),
+			Piece::Class(Class { set, range, negated }) => {
+				let negated_flag = if negated { FLAG_NEGATED } else { 0 };
+				(TAG_CLASS | negated_flag | extra_flag(range), Elements(set, range))
+			}
+			Piece::Globstar => (TAG_GLOBSTAR, Elements(&[], None)),
+			Piece::Alternation(Alternation { alternatives, separator }) => {
+				(TAG_ALTERNATION | extra_flag(separator), Elements(alternatives, separator))
+			}
+		};
+		(tag, elements).serialize(serializer)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, T: Deserialize<'de> + Clone> Deserialize<'de> for VecPieces<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let encoded = Vec::<(u8, Vec<T>)>::deserialize(deserializer)?;
+		let mut pieces = VecPieces::new();
+		for (tag, elements) in encoded.iter() {
+			let invalid = move || de::Error::invalid_value(de::Unexpected::Unsigned((*tag).into()), &"pattern piece");
+			let (kind, negated, has_extra) = (tag & 0xf, tag & FLAG_NEGATED != 0, tag & FLAG_EXTRA != 0);
+			let (set, extra) = match elements.split_last() {
+				Some((extra, set)) if has_extra => (set, Some(extra)),
+				None if has_extra => return Err(invalid()),
+				_ => (elements.as_slice(), None),
+			};
+			let piece = match kind {
+				TAG_LITERAL if !negated && !has_extra => Piece::Literal(set),
+				TAG_WILDCARD if *tag == kind && set.is_empty() => Piece::Wildcard,
+				TAG_ANY if *tag == kind && set.is_empty() => Piece::Any,
+				TAG_CLASS => Piece::Class(Class { set, range: extra, negated }),
+				TAG_GLOBSTAR if *tag == kind && set.is_empty() => Piece::Globstar,
+				TAG_ALTERNATION if !negated => Piece::Alternation(Alternation { alternatives: set, separator: extra }),
+				_ => return Err(invalid()),
+			};
+			pieces.push_piece(piece);
+		}
+		Ok(pieces)
+	}
+}
+
+/// Serialized as a sequence of rules, each a pattern and its value.
+#[cfg(feature = "alloc")]
+impl<P: Serialize, T, V: Serialize> Serialize for RuleSet<P, T, V> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_seq(self.rules())
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, P: Deserialize<'de>, T, V: Deserialize<'de>> Deserialize<'de> for RuleSet<P, T, V> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Vec::deserialize(deserializer).map(Self::from_iter)
+	}
+}
+
+/// Serialized as its [`RuleSet`], from which the rest is rebuilt when deserialized.
+#[cfg(feature = "alloc")]
+impl<P: Serialize + Pieces<T>, T: PartialEq, V: Serialize, M: KeyMatcher<T>> Serialize for PatternSet<P, T, V, M> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.rules().serialize(serializer)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, P, T, V, M> Deserialize<'de> for PatternSet<P, T, V, M>
+where
+	P: Deserialize<'de> + Pieces<T>,
+	T: PartialEq,
+	V: Deserialize<'de>,
+	M: KeyMatcher<T> + Default,
+{
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		RuleSet::deserialize(deserializer).map(move |rules| Self::new(rules, M::default()))
+	}
+}
+
+#[cfg(feature = "alloc")]
+#[cfg(test)]
+mod tests {
+	use alloc::{
+		string::{String, ToString},
+		vec::Vec,
+	};
+
+	use crate::*;
+	use u8_buf::*;
+	use vec_buf::*;
+
+	#[test]
+	fn u8_patterns_round_trip() {
+		for pattern in [b"maps/*.bsp".as_ref(), b"**/[!a-z]?_{a,b}/**", b"\\*literal\\?", b""] {
+			let pattern = Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+			let json = serde_json::to_string(&pattern).unwrap();
+			assert_eq!(serde_json::from_str::<Pattern<U8Pieces, u8>>(&json).unwrap(), pattern, "{json}");
+		}
+	}
+
+	#[test]
+	fn char_patterns_round_trip() {
+		for pattern in ["maps/*.bsp", "**/[!а-я-]?_{a,b}/**", "[é]*ß", ""] {
+			let pattern: Vec<char> = pattern.chars().collect();
+			let pattern = Pattern::<VecPieces<char>, char>::parse_with(&pattern, &Syntax::<char>::PATH_GLOB);
+			let json = serde_json::to_string(&pattern).unwrap();
+			let round_trip = serde_json::from_str::<Pattern<VecPieces<char>, char>>(&json).unwrap();
+			assert_eq!(round_trip, pattern, "{json}");
+			assert_eq!(serde_json::to_string(&round_trip).unwrap(), json);
+		}
+	}
+
+	#[test]
+	fn invalid_patterns_are_rejected() {
+		let u8_pattern = move |json: &str| {
+			serde_json::from_str::<Pattern<U8Pieces, u8>>(json).map_err(move |e| e.to_string())
+		};
+		let char_pattern = move |json: &str| serde_json::from_str::<Pattern<VecPieces<char>, char>>(json).map(drop);
+
+		// Patterns are stored as pieces, not in the syntax that they're parsed from.
+		assert!(u8_pattern(r#"[0, "maps/*.bsp"]"#).is_err());
+		assert!(char_pattern(r#"[0, "maps/*.bsp"]"#).is_err());
+		// Unknown tags and flags.
+		assert!(u8_pattern("[0, [9]]").unwrap_err().contains("invalid encoded pattern pieces"));
+		assert!(u8_pattern("[255, []]").is_err());
+		assert!(char_pattern(r#"[0, [[9, []]]]"#).is_err());
+		assert!(char_pattern(r#"[0, [[1, ["a"]]]]"#).is_err());
+		assert!(char_pattern(r#"[0, [[35, []]]]"#).is_err());
+		assert!(char_pattern(r#"[0, [[3, ["a"]], [1, []]]]"#).is_ok());
+
+		let json = serde_json::to_string(&Pattern::<U8Pieces, u8>::parse(b"a*", &b'*')).unwrap();
+		let truncated = String::from(&json[..json.len() - 4]) + "]]";
+		assert!(u8_pattern(&truncated).is_err(), "{truncated}");
+	}
+
+	#[test]
+	fn pattern_sets_round_trip() {
+		let parse = move |pattern: &[u8]| Pattern::<U8Pieces, u8>::parse_with(pattern, &Syntax::<u8>::PATH_GLOB);
+		let set = PatternSet::<U8Pieces, u8, u32, PathMatch>::new(
+			[(parse(b"maps/*.bsp"), 1), (parse(b"**/*.nav"), 2)].into_iter().collect(),
+			PathMatch,
+		);
+		let json = serde_json::to_string(&set).unwrap();
+		let round_trip = serde_json::from_str::<PatternSet<U8Pieces, u8, u32, PathMatch>>(&json).unwrap();
+		assert_eq!(round_trip.rules().rules().iter().map(move |(_, value)| *value).collect::<Vec<_>>(), [1, 2]);
+		assert_eq!(serde_json::to_string(&round_trip).unwrap(), json);
+	}
+}
@@ -0,0 +1,370 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::char::REPLACEMENT_CHARACTER;
+use core::{
+	fmt::{self, Write},
+	marker::PhantomData,
+	slice,
+};
+
+use crate::{
+	Alternation, Class, Pattern, PatternFlags, Piece, Pieces,
+};
+
+/// Elements that have a special meaning in a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Syntax<T> {
+	/// Element that matches any sequence of elements, including an empty one.
+	pub wildcard: T,
+	/// Element that matches any single element.
+	pub any: Option<T>,
+	pub class: Option<ClassSyntax<T>>,
+	pub alternation: Option<AlternationSyntax<T>>,
+	/// Element that separates the segments of a path, making patterns path-aware.
+	///
+	/// In a path-aware pattern, two or more wildcards that make up a whole segment match any number of segments.
+	pub separator: Option<T>,
+	/// Element that makes the element after it literal, like `\*`.
+	pub escape: Option<T>,
+}
+
+/// Elements that denote a [`Class`] in a pattern, like `[a-z]` or `[!0-9]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClassSyntax<T> {
+	pub open: T,
+	pub close: T,
+	/// Element between the bounds of a range, unless it's the first or last element of the class.
+	pub range: T,
+	/// Element that negates the class, if it's the first element of the class.
+	pub negate: T,
+}
+
+/// Elements that denote an [`Alternation`] in a pattern, like `{vmt,vtf}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlternationSyntax<T> {
+	pub open: T,
+	pub close: T,
+	pub separator: T,
+}
+
+impl<T> Syntax<T> {
+	/// Syntax in which `wildcard` is the only element with a special meaning.
+	#[inline]
+	pub const fn wildcard(wildcard: T) -> Self {
+		Self {
+			wildcard,
+			any: None,
+			class: None,
+			alternation: None,
+			separator: None,
+			escape: None,
+		}
+	}
+}
+
+impl Syntax<u8> {
+	/// Shell-like syntax, with `*`, `?`, `[...]`, `{...,...}` and `\` escapes.
+	pub const GLOB: Self = Self {
+		wildcard: b'*',
+		any: Some(b'?'),
+		class: Some(ClassSyntax {
+			open: b'[',
+			close: b']',
+			range: b'-',
+			negate: b'!',
+		}),
+		alternation: Some(AlternationSyntax {
+			open: b'{',
+			close: b'}',
+			separator: b',',
+		}),
+		separator: None,
+		escape: Some(b'\\'),
+	};
+
+	/// [`GLOB`](Self::GLOB), with `/` separating the segments of a path, and `**` matching any number of them.
+	pub const PATH_GLOB: Self = Self {
+		separator: Some(b'/'),
+		..Self::GLOB
+	};
+}
+
+impl Syntax<char> {
+	/// Shell-like syntax, with `*`, `?`, `[...]`, `{...,...}` and `\` escapes.
+	pub const GLOB: Self = Self {
+		wildcard: '*',
+		any: Some('?'),
+		class: Some(ClassSyntax {
+			open: '[',
+			close: ']',
+			range: '-',
+			negate: '!',
+		}),
+		alternation: Some(AlternationSyntax {
+			open: '{',
+			close: '}',
+			separator: ',',
+		}),
+		separator: None,
+		escape: Some('\\'),
+	};
+
+	/// [`GLOB`](Self::GLOB), with `/` separating the segments of a path, and `**` matching any number of them.
+	pub const PATH_GLOB: Self = Self {
+		separator: Some('/'),
+		..Self::GLOB
+	};
+}
+
+impl<'a, T: 'a + PartialEq, P: FromIterator<Piece<'a, T>>> Pattern<P, T> {
+	/// Parse `pattern`, in which elements have the special meanings given by `syntax`.
+	///
+	/// A class or alternation that isn't closed is parsed as a literal, as is an escape at the end of `pattern`. The
+	/// alternatives of an alternation are literals, without escapes, and alternations don't nest. Wildcards at the
+	/// start or end of a pattern that isn't path-aware are parsed into its [`PatternFlags`], unless they're escaped.
+	pub fn parse_with(pattern: &'a [T], syntax: &Syntax<T>) -> Self {
+		if syntax.separator.is_some() {
+			return Self {
+				flags: PatternFlags::empty().with_path_aware().with_end_anchored(),
+				pieces: Tokens { pattern, rest: pattern, syntax }.collect(),
+				piece_t: PhantomData,
+			}
+		}
+
+		let is_wildcard = move |t: &T| *t == syntax.wildcard;
+		let start = pattern.iter().position(move |t| !is_wildcard(t)).unwrap_or(pattern.len());
+		let mut end = pattern.iter().rposition(move |t| !is_wildcard(t)).map_or(start, move |i| i + 1);
+		if end < pattern.len() && syntax.ends_with_escape(&pattern[..end]) {
+			end += 1;
+		}
+
+		let mut flags = PatternFlags::empty();
+		if start > 0 {
+			flags = flags.with_start_unanchored();
+		}
+		if pattern.is_empty() || (start < end && end == pattern.len()) {
+			flags = flags.with_end_anchored();
+		}
+
+		Self {
+			flags,
+			pieces: Tokens { pattern, rest: &pattern[start..end], syntax }.collect(),
+			piece_t: PhantomData,
+		}
+	}
+}
+
+impl<T: PartialEq, P: Pieces<T>> Pattern<P, T> {
+	/// Write the pattern with `syntax`, one element at a time, so that [`parse_with`](Self::parse_with) parses it back
+	/// into an equivalent pattern.
+	///
+	/// Literal elements with a special meaning are escaped, if `syntax` has an escape. Pieces that `syntax` has no
+	/// elements for are written as wildcards.
+	pub fn write_with<E>(&self, syntax: &Syntax<T>, mut write: impl FnMut(&T) -> Result<(), E>) -> Result<(), E> {
+		let class_syntax = syntax.class.as_ref();
+		let alternation_syntax = syntax.alternation.as_ref();
+		if self.flags.is_start_unanchored() {
+			write(&syntax.wildcard)?;
+		}
+		for piece in self.pieces.pieces() {
+			match (piece, class_syntax) {
+				(Piece::Literal(literal), _) => for t in literal {
+					let is_special = *t == syntax.wildcard
+						|| syntax.any.as_ref() == Some(t)
+						|| syntax.escape.as_ref() == Some(t)
+						|| class_syntax.is_some_and(move |class| class.open == *t)
+						|| alternation_syntax.is_some_and(move |alternation| alternation.open == *t);
+					if let Some(escape) = syntax.escape.as_ref().filter(move |_| is_special) {
+						write(escape)?;
+					}
+					write(t)?;
+				}
+				(Piece::Wildcard, _) => write(&syntax.wildcard)?,
+				(Piece::Globstar, _) => {
+					write(&syntax.wildcard)?;
+					if let Some(separator) = syntax.separator.as_ref() {
+						write(&syntax.wildcard)?;
+						write(separator)?;
+					}
+				}
+				(Piece::Any, _) => write(syntax.any.as_ref().unwrap_or(&syntax.wildcard))?,
+				(Piece::Class(class), Some(class_syntax)) => {
+					write(&class_syntax.open)?;
+					if class.negated {
+						write(&class_syntax.negate)?;
+					}
+					class.set.iter().try_for_each(&mut write)?;
+					write(&class_syntax.close)?;
+				}
+				(Piece::Class(_), None) => write(&syntax.wildcard)?,
+				(Piece::Alternation(alternation), _) => match alternation_syntax {
+					Some(alternation_syntax) => {
+						write(&alternation_syntax.open)?;
+						for (i, alternative) in alternation.alternatives().enumerate() {
+							if i > 0 {
+								write(&alternation_syntax.separator)?;
+							}
+							alternative.iter().try_for_each(&mut write)?;
+						}
+						write(&alternation_syntax.close)?;
+					}
+					None => write(&syntax.wildcard)?,
+				},
+			}
+		}
+		if !self.flags.is_end_anchored() && !self.flags.is_path_aware() {
+			write(&syntax.wildcard)?;
+		}
+		Ok(())
+	}
+
+	/// Get the pattern written with `syntax`, as by [`write_with`](Self::write_with).
+	#[cfg(feature = "alloc")]
+	pub fn to_vec_with(&self, syntax: &Syntax<T>) -> Vec<T>
+	where
+		T: Clone,
+	{
+		let mut result = Vec::new();
+		let Ok(()) = self.write_with(syntax, |t| {
+			result.push(t.clone());
+			Ok::<_, core::convert::Infallible>(())
+		});
+		result
+	}
+}
+
+/// Written with [`Syntax::<u8>::GLOB`], or [`Syntax::<u8>::PATH_GLOB`] if path-aware. Bytes that aren't valid UTF-8
+/// are written as [`REPLACEMENT_CHARACTER`].
+#[cfg(feature = "alloc")]
+impl<P: Pieces<u8>> fmt::Display for Pattern<P, u8> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let syntax = if self.flags.is_path_aware() { &Syntax::<u8>::PATH_GLOB } else { &Syntax::<u8>::GLOB };
+		for chunk in self.to_vec_with(syntax).utf8_chunks() {
+			f.write_str(chunk.valid())?;
+			if !chunk.invalid().is_empty() {
+				f.write_char(REPLACEMENT_CHARACTER)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Written with [`Syntax::<char>::GLOB`], or [`Syntax::<char>::PATH_GLOB`] if path-aware.
+impl<P: Pieces<char>> fmt::Display for Pattern<P, char> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let syntax = if self.flags.is_path_aware() { &Syntax::<char>::PATH_GLOB } else { &Syntax::<char>::GLOB };
+		self.write_with(syntax, move |c| f.write_char(*c))
+	}
+}
+
+impl<T: PartialEq> Syntax<T> {
+	/// Whether the element after `pattern` is escaped, that is, whether `pattern` ends with an unescaped escape.
+	fn ends_with_escape(&self, pattern: &[T]) -> bool {
+		self.escape.as_ref().is_some_and(move |escape| {
+			pattern.iter().rev().take_while(move |t| *t == escape).count() % 2 == 1
+		})
+	}
+}
+
+/// Iterator over the [`Piece`]s of a pattern.
+struct Tokens<'a, 's, T> {
+	pattern: &'a [T],
+	rest: &'a [T],
+	syntax: &'s Syntax<T>,
+}
+
+impl<'a, T: PartialEq> Tokens<'a, '_, T> {
+	/// Parse the class at the start of `pattern`, returning it along with the rest of `pattern`.
+	fn class_at(&self, pattern: &'a [T]) -> Option<(Class<'a, T>, &'a [T])> {
+		let syntax = self.syntax.class.as_ref()?;
+		let body = pattern.strip_prefix(slice::from_ref(&syntax.open))?;
+		let (negated, body) = match body.strip_prefix(slice::from_ref(&syntax.negate)) {
+			Some(body) => (true, body),
+			None => (false, body),
+		};
+		// A closing element right at the start is a member, so that it can be in the class.
+		let close = 1 + body.get(1..)?.iter().position(move |t| *t == syntax.close)?;
+		let set = &body[..close];
+		let class = Class {
+			set,
+			range: set.iter().find(move |t| **t == syntax.range),
+			negated,
+		};
+		Some((class, &body[close + 1..]))
+	}
+
+	/// Parse the alternation at the start of `pattern`, returning it along with the rest of `pattern`.
+	fn alternation_at(&self, pattern: &'a [T]) -> Option<(Alternation<'a, T>, &'a [T])> {
+		let syntax = self.syntax.alternation.as_ref()?;
+		let body = pattern.strip_prefix(slice::from_ref(&syntax.open))?;
+		let close = body.iter().position(move |t| *t == syntax.close)?;
+		let alternatives = &body[..close];
+		let alternation = Alternation {
+			alternatives,
+			separator: alternatives.iter().find(move |t| **t == syntax.separator),
+		};
+		Some((alternation, &body[close + 1..]))
+	}
+
+	fn is_special(&self, pattern: &'a [T]) -> bool {
+		let Some(first) = pattern.first() else {
+			return false
+		};
+		*first == self.syntax.wildcard
+			|| self.syntax.any.as_ref() == Some(first)
+			|| self.syntax.escape.as_ref() == Some(first)
+			|| self.class_at(pattern).is_some()
+			|| self.alternation_at(pattern).is_some()
+	}
+}
+
+impl<'a, T: PartialEq> Iterator for Tokens<'a, '_, T> {
+	type Item = Piece<'a, T>;
+	fn next(&mut self) -> Option<Self::Item> {
+		let (first, rest) = self.rest.split_first()?;
+		if self.syntax.escape.as_ref() == Some(first) && !rest.is_empty() {
+			let literal;
+			(literal, self.rest) = rest.split_at(1);
+			return Some(Piece::Literal(literal))
+		}
+		if *first == self.syntax.wildcard {
+			let wildcards = rest.iter().take_while(|t| **t == self.syntax.wildcard).count();
+			let before = &self.pattern[..self.pattern.len() - self.rest.len()];
+			self.rest = &rest[wildcards..];
+			let Some(separator) = self.syntax.separator.as_ref().filter(move |_| wildcards > 0) else {
+				return Some(Piece::Wildcard)
+			};
+			if before.last().is_some_and(move |t| t != separator) {
+				return Some(Piece::Wildcard)
+			}
+			// The separator after a globstar is part of it, so that `a/**/b` matches `a/b`.
+			match self.rest.split_first() {
+				Some((t, rest)) if t == separator => self.rest = rest,
+				Some(_) => return Some(Piece::Wildcard),
+				// At the end, the last wildcard is left to match the last segment, so that `a/**` matches `a/b`.
+				None => self.rest = &self.pattern[self.pattern.len() - 1..],
+			}
+			return Some(Piece::Globstar)
+		}
+		if self.syntax.any.as_ref() == Some(first) {
+			self.rest = rest;
+			return Some(Piece::Any)
+		}
+		if let Some((class, rest)) = self.class_at(self.rest) {
+			self.rest = rest;
+			return Some(Piece::Class(class))
+		}
+		if let Some((alternation, rest)) = self.alternation_at(self.rest) {
+			self.rest = rest;
+			return Some(Piece::Alternation(alternation))
+		}
+
+		let len = 1 + (1..self.rest.len())
+			.position(|i| self.is_special(&self.rest[i..]))
+			.unwrap_or(self.rest.len() - 1);
+		let literal;
+		(literal, self.rest) = self.rest.split_at(len);
+		Some(Piece::Literal(literal))
+	}
+}
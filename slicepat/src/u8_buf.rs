@@ -1,10 +1,21 @@
 use alloc::vec::Vec;
-use core::{
-	fmt,
-	mem::size_of,
+use core::fmt;
+
+use crate::{
+	Alternation, Class, Piece, Pieces,
 };
 
-use crate::Pieces;
+pub(crate) const TAG_LITERAL: u8 = 0;
+pub(crate) const TAG_WILDCARD: u8 = 1;
+pub(crate) const TAG_ANY: u8 = 2;
+pub(crate) const TAG_CLASS: u8 = 3;
+pub(crate) const TAG_GLOBSTAR: u8 = 4;
+pub(crate) const TAG_ALTERNATION: u8 = 5;
+
+const CLASS_NEGATED: u8 = 1 << 0;
+const CLASS_HAS_RANGE: u8 = 1 << 1;
+
+const ALTERNATION_HAS_SEPARATOR: u8 = 1 << 0;
 
 #[derive(Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
@@ -19,16 +30,57 @@ impl U8Pieces {
 		Self(Vec::with_capacity(capacity))
 	}
 	
+	/// Push a literal piece, preceded by a wildcard unless it's the first piece.
+	///
+	/// Empty pieces are skipped.
 	pub fn push(&mut self, piece: &[u8]) {
-		let piece_len = piece.len();
-		if piece_len > 0 {
-			let piece_len_buf = piece_len.to_ne_bytes();
-			self.0.reserve(piece_len_buf.len() + piece_len);
-			self.0.extend_from_slice(&piece_len_buf);
-			self.0.extend_from_slice(piece);
+		if !piece.is_empty() {
+			if !self.0.is_empty() {
+				self.push_piece(Piece::Wildcard);
+			}
+			self.push_piece(Piece::Literal(piece));
+		}
+	}
+
+	pub fn push_piece(&mut self, piece: Piece<'_, u8>) {
+		match piece {
+			Piece::Literal(literal) => {
+				self.0.push(TAG_LITERAL);
+				self.push_len_prefixed(literal);
+			}
+			Piece::Wildcard => self.0.push(TAG_WILDCARD),
+			Piece::Globstar => self.0.push(TAG_GLOBSTAR),
+			Piece::Any => self.0.push(TAG_ANY),
+			Piece::Class(class) => {
+				let mut flags = 0;
+				if class.negated {
+					flags |= CLASS_NEGATED;
+				}
+				if class.range.is_some() {
+					flags |= CLASS_HAS_RANGE;
+				}
+				self.0.extend_from_slice(&[TAG_CLASS, flags, class.range.copied().unwrap_or(0)]);
+				self.push_len_prefixed(class.set);
+			}
+			Piece::Alternation(alternation) => {
+				let flags = if alternation.separator.is_some() { ALTERNATION_HAS_SEPARATOR } else { 0 };
+				self.0.extend_from_slice(&[TAG_ALTERNATION, flags, alternation.separator.copied().unwrap_or(0)]);
+				self.push_len_prefixed(alternation.alternatives);
+			}
 		}
 	}
 
+	/// Push `bytes`, preceded by their length as a LEB128 varint.
+	fn push_len_prefixed(&mut self, bytes: &[u8]) {
+		let mut len = bytes.len();
+		while len >= 0x80 {
+			self.0.push(len as u8 | 0x80);
+			len >>= 7;
+		}
+		self.0.push(len as u8);
+		self.0.extend_from_slice(bytes);
+	}
+
 	pub fn capacity(&self) -> usize {
 		self.0.capacity()
 	}
@@ -36,8 +88,40 @@ impl U8Pieces {
 	pub fn reserve(&mut self, total_len: usize) {
 		self.0.reserve(total_len);
 	}
+
+	/// Get the encoded pieces, which are the same on every platform.
+	#[inline]
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+
+	#[inline]
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.0
+	}
+
+	/// Wrap pieces encoded like [`as_bytes`](Self::as_bytes) returns them, if they're valid.
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, InvalidPieces> {
+		let mut iter = U8PiecesIter(&bytes);
+		while !iter.0.is_empty() {
+			iter.next().ok_or(InvalidPieces)?;
+		}
+		Ok(Self(bytes))
+	}
+}
+
+/// Error returned by [`U8Pieces::from_bytes`] for bytes that aren't encoded pieces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InvalidPieces;
+
+impl fmt::Display for InvalidPieces {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("invalid encoded pattern pieces")
+	}
 }
 
+impl core::error::Error for InvalidPieces {}
+
 impl Pieces<u8> for U8Pieces {
 	type Iter<'a> = U8PiecesIter<'a>;
 	fn pieces(&self) -> Self::Iter<'_> {
@@ -55,9 +139,19 @@ impl<'a> FromIterator<&'a [u8]> for U8Pieces {
 	}
 }
 
+impl<'a> FromIterator<Piece<'a, u8>> for U8Pieces {
+	fn from_iter<T: IntoIterator<Item = Piece<'a, u8>>>(iter: T) -> Self {
+		let mut result = Self::new();
+		for piece in iter {
+			result.push_piece(piece);
+		}
+		result
+	}
+}
+
 impl<'a, T: AsRef<[&'a [u8]]>> From<T> for U8Pieces {
 	fn from(value: T) -> Self {
-		let capacity = value.as_ref().iter().map(move |piece| size_of::<usize>() + piece.len()).sum();
+		let capacity = value.as_ref().iter().map(move |piece| 3 + piece.len()).sum();
 		let mut result = Self::with_capacity(capacity);
 		for piece in value.as_ref() {
 			result.push(piece);
@@ -82,29 +176,106 @@ pub struct U8PiecesIter<'a>(&'a [u8]);
 
 impl<'a> U8PiecesIter<'a> {
 	/// # Safety
-	/// `inner` must be a slice that contains sequences of [`u8`]s, with each sequence prepended with its length in
-	/// [`usize`] encoded in native-endian.
+	/// `inner` must be a slice that contains encoded pieces, each starting with a tag. Literals are followed by their
+	/// length encoded as a LEB128 varint, and their bytes. Classes are followed by flags, the byte that denotes a
+	/// range, and their set, encoded like literals. Alternations are followed by flags, their separator and
+	/// their alternatives, encoded like literals.
 	#[inline]
 	pub const unsafe fn new_unchecked(inner: &'a [u8]) -> Self {
 		Self(inner)
 	}
 }
 
+impl<'a> U8PiecesIter<'a> {
+	fn next_len_prefixed(&mut self) -> Option<&'a [u8]> {
+		let mut len = 0usize;
+		let mut shift = 0;
+		loop {
+			let byte;
+			(byte, self.0) = self.0.split_first()?;
+			let bits = usize::from(byte & 0x7f);
+			let shifted = bits.checked_shl(shift).filter(move |shifted| shifted >> shift == bits)?;
+			len |= shifted;
+			if byte & 0x80 == 0 {
+				break
+			}
+			shift += 7;
+		}
+		let bytes;
+		(bytes, self.0) = self.0.split_at_checked(len)?;
+		Some(bytes)
+	}
+}
+
 impl<'a> Iterator for U8PiecesIter<'a> {
-	type Item = &'a [u8];
+	type Item = Piece<'a, u8>;
 	fn next(&mut self) -> Option<Self::Item> {
-		let (piece_len, after_len) = self.0.split_at_checked(size_of::<usize>())?;
-		let piece_len = usize::from_ne_bytes(piece_len.try_into().ok()?);
-		let piece;
-		(piece, self.0) = after_len.split_at_checked(piece_len)?;
-		Some(piece)
+		let tag;
+		(tag, self.0) = self.0.split_first()?;
+		match *tag {
+			TAG_LITERAL => self.next_len_prefixed().map(Piece::Literal),
+			TAG_WILDCARD => Some(Piece::Wildcard),
+			TAG_GLOBSTAR => Some(Piece::Globstar),
+			TAG_ANY => Some(Piece::Any),
+			TAG_CLASS => {
+				let ([flags, range], after_header) = self.0.split_first_chunk::<2>()?;
+				self.0 = after_header;
+				let set = self.next_len_prefixed()?;
+				Some(Piece::Class(Class {
+					set,
+					range: (flags & CLASS_HAS_RANGE != 0).then_some(range),
+					negated: flags & CLASS_NEGATED != 0,
+				}))
+			}
+			TAG_ALTERNATION => {
+				let ([flags, separator], after_header) = self.0.split_first_chunk::<2>()?;
+				self.0 = after_header;
+				let alternatives = self.next_len_prefixed()?;
+				Some(Piece::Alternation(Alternation {
+					alternatives,
+					separator: (flags & ALTERNATION_HAS_SEPARATOR != 0).then_some(separator),
+				}))
+			}
+			_ => None,
+		}
 	}
 }
 
 #[test]
 fn iter_buf_pieces() {
-	let pieces_array = [b"one".as_ref(), b"tour"];
-	let pieces = U8Pieces::from(pieces_array);
-	assert_eq!(pieces.pieces().count(), pieces_array.len());
-	assert_eq!(pieces.pieces().zip(pieces_array).find(move |(piece, orig_piece)| piece != orig_piece), None);
+	let pieces = U8Pieces::from([b"one".as_ref(), b"tour"]);
+	let expected = [Piece::Literal(b"one".as_ref()), Piece::Wildcard, Piece::Literal(b"tour")];
+	assert_eq!(pieces.pieces().count(), expected.len());
+	assert_eq!(pieces.pieces().zip(expected).find(move |(piece, expected)| piece != expected), None);
+}
+
+#[test]
+fn buf_bytes_round_trip() {
+	let long = [b'a'; 300];
+	let pieces = U8Pieces::from([b"one".as_ref(), &long]);
+	assert_eq!(&pieces.as_bytes()[..6], [TAG_LITERAL, 3, b'o', b'n', b'e', TAG_WILDCARD]);
+	assert_eq!(&pieces.as_bytes()[6..9], [TAG_LITERAL, 0xac, 0x02]);
+	assert_eq!(U8Pieces::from_bytes(pieces.as_bytes().to_vec()), Ok(pieces));
+	assert_eq!(U8Pieces::from_bytes([TAG_LITERAL, 4, b'o', b'n', b'e'].to_vec()), Err(InvalidPieces));
+	assert_eq!(U8Pieces::from_bytes([TAG_WILDCARD, 0xff].to_vec()), Err(InvalidPieces));
+	let overflowing = [TAG_LITERAL, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+	assert_eq!(U8Pieces::from_bytes(overflowing.to_vec()), Err(InvalidPieces));
+}
+
+#[test]
+fn iter_buf_classes() {
+	let pieces: U8Pieces = [
+		Piece::Literal(b"b".as_ref()),
+		Piece::Any,
+		Piece::Class(Class { set: b"a-z", range: Some(&b'-'), negated: true }),
+		Piece::Alternation(Alternation { alternatives: b"vmt,vtf", separator: Some(&b',') }),
+		Piece::Alternation(Alternation { alternatives: b"vtx", separator: None }),
+	].into_iter().collect();
+	assert_eq!(pieces.pieces().collect::<Vec<_>>(), [
+		Piece::Literal(b"b".as_ref()),
+		Piece::Any,
+		Piece::Class(Class { set: b"a-z", range: Some(&b'-'), negated: true }),
+		Piece::Alternation(Alternation { alternatives: b"vmt,vtf", separator: Some(&b',') }),
+		Piece::Alternation(Alternation { alternatives: b"vtx", separator: None }),
+	]);
 }
@@ -0,0 +1,321 @@
+//! [`Matcher`]s that compare UTF-8 case-insensitively, behind the `unicode` feature.
+
+#[cfg(feature = "normalization")]
+use alloc::{
+	borrow::Cow,
+	string::String,
+};
+
+#[cfg(feature = "normalization")]
+use unicode_normalization::{
+	IsNormalized, UnicodeNormalization,
+	is_nfc_quick, is_nfd_quick,
+};
+
+use crate::{
+	KeyMatcher, Matcher,
+	find_by_first_byte, single_elements,
+};
+
+/// Key of every byte that isn't ASCII, as its case depends on the rest of its character.
+const NON_ASCII_KEY: u8 = 0x80;
+
+/// [`Matcher`] that ignores the case of characters in UTF-8, or of [`char`]s, with simple case folding.
+///
+/// Characters are only equal to those with the same length in UTF-8, as are nearly all pairs of cases, so that
+/// patterns can be matched byte by byte. [`Piece::Any`](crate::Piece::Any) and [`Piece::Class`](crate::Piece::Class)
+/// match whole characters, and bytes that aren't valid UTF-8 one at a time, which are only equal to themselves.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnicodeCaseInsensitive;
+
+impl Matcher<u8> for UnicodeCaseInsensitive {
+	fn is_equal(&self, a: &[u8], b: &[u8]) -> bool {
+		is_equal_utf8(a, b, move |a, b| fold(a) == fold(b))
+	}
+
+	fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+		find_utf8(self, needle, haystack, needle[0].to_ascii_lowercase(), needle[0].to_ascii_uppercase())
+	}
+
+	fn element_len(&self, haystack: &[u8]) -> usize {
+		next_char(haystack).map_or(1, move |(_, len)| len)
+	}
+
+	fn is_in_range(&self, low: &[u8], high: &[u8], t: &[u8]) -> bool {
+		is_in_range_utf8(low, high, t, is_in_range_ignore_case)
+	}
+}
+
+impl KeyMatcher<u8> for UnicodeCaseInsensitive {
+	type Key = u8;
+	fn key(&self, t: &u8) -> Self::Key {
+		if t.is_ascii() { t.to_ascii_lowercase() } else { NON_ASCII_KEY }
+	}
+}
+
+impl Matcher<char> for UnicodeCaseInsensitive {
+	fn is_equal(&self, a: &[char], b: &[char]) -> bool {
+		a.len() == b.len() && a.iter().zip(b).all(move |(a, b)| fold(*a) == fold(*b))
+	}
+
+	fn is_in_range(&self, low: &[char], high: &[char], t: &[char]) -> bool {
+		single_elements(low, high, t).is_some_and(move |(low, high, t)| is_in_range_ignore_case(*low, *high, *t))
+	}
+}
+
+impl KeyMatcher<char> for UnicodeCaseInsensitive {
+	type Key = char;
+	fn key(&self, t: &char) -> Self::Key {
+		fold(*t)
+	}
+}
+
+/// [`UnicodeCaseInsensitive`], with `/` and `\` being equal separators, like [`PathMatch`](crate::PathMatch).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnicodePathMatch;
+
+impl Matcher<u8> for UnicodePathMatch {
+	fn is_equal(&self, a: &[u8], b: &[u8]) -> bool {
+		is_equal_utf8(a, b, move |a, b| matches!((a, b), ('/' | '\\', '/' | '\\')) || fold(a) == fold(b))
+	}
+
+	fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+		let (first, second) = match needle[0] {
+			b'/' | b'\\' => (b'/', b'\\'),
+			first => (first.to_ascii_lowercase(), first.to_ascii_uppercase()),
+		};
+		find_utf8(self, needle, haystack, first, second)
+	}
+
+	fn element_len(&self, haystack: &[u8]) -> usize {
+		UnicodeCaseInsensitive.element_len(haystack)
+	}
+
+	fn is_in_range(&self, low: &[u8], high: &[u8], t: &[u8]) -> bool {
+		is_in_range_utf8(low, high, t, move |low, high, t| match t {
+			'/' | '\\' => (low..=high).contains(&'/') || (low..=high).contains(&'\\'),
+			t => is_in_range_ignore_case(low, high, t),
+		})
+	}
+
+	fn is_separator(&self, t: &u8) -> bool {
+		matches!(t, b'/' | b'\\')
+	}
+}
+
+impl KeyMatcher<u8> for UnicodePathMatch {
+	type Key = u8;
+	fn key(&self, t: &u8) -> Self::Key {
+		match t {
+			b'\\' => b'/',
+			t => UnicodeCaseInsensitive.key(t),
+		}
+	}
+}
+
+/// Fold the case of `c`, to its lowercase if that's a single character.
+fn fold(c: char) -> char {
+	let mut lower = c.to_lowercase();
+	match (lower.next(), lower.next()) {
+		(Some(lower), None) => lower,
+		_ => c,
+	}
+}
+
+fn upper(c: char) -> char {
+	let mut upper = c.to_uppercase();
+	match (upper.next(), upper.next()) {
+		(Some(upper), None) => upper,
+		_ => c,
+	}
+}
+
+fn is_in_range_ignore_case(low: char, high: char, t: char) -> bool {
+	let range = low..=high;
+	range.contains(&t) || range.contains(&fold(t)) || range.contains(&upper(t))
+}
+
+/// Returns `true` if the character in `t` is in the range from the one in `low` to the one in `high`, as decided by
+/// `is_in_range_char`, or if they're bytes that aren't UTF-8, if the byte in `t` is in the range between the others.
+fn is_in_range_utf8(low: &[u8], high: &[u8], t: &[u8], is_in_range_char: impl Fn(char, char, char) -> bool) -> bool {
+	let decode = move |bytes: &[u8]| match next_char(bytes) {
+		Some((Some(c), len)) if len == bytes.len() => Some(c),
+		_ => None,
+	};
+	match (decode(low), decode(high), decode(t)) {
+		(Some(low), Some(high), Some(t)) => is_in_range_char(low, high, t),
+		_ => single_elements(low, high, t).is_some_and(move |(low, high, t)| (low..=high).contains(&t)),
+	}
+}
+
+/// Compare `a` and `b` character by character with `is_equal_char`, and byte by byte where they aren't UTF-8.
+fn is_equal_utf8(mut a: &[u8], mut b: &[u8], is_equal_char: impl Fn(char, char) -> bool) -> bool {
+	if a.len() != b.len() {
+		return false
+	}
+	while let (Some((a_char, a_len)), Some((b_char, b_len))) = (next_char(a), next_char(b)) {
+		if a_len != b_len {
+			return false
+		}
+		let is_equal = match (a_char, b_char) {
+			(Some(a_char), Some(b_char)) => a_char == b_char || is_equal_char(a_char, b_char),
+			(None, None) => a[0] == b[0],
+			_ => false,
+		};
+		if !is_equal {
+			return false
+		}
+		(a, b) = (&a[a_len..], &b[b_len..]);
+	}
+	true
+}
+
+/// Decode the first character of `bytes`, returning it, or [`None`] if the first byte doesn't start a valid one,
+/// along with the number of bytes that it takes up.
+fn next_char(bytes: &[u8]) -> Option<(Option<char>, usize)> {
+	let len = match *bytes.first()? {
+		0x00..=0x7f => return Some((Some(char::from(bytes[0])), 1)),
+		0xc2..=0xdf => 2,
+		0xe0..=0xef => 3,
+		0xf0..=0xf4 => 4,
+		_ => return Some((None, 1)),
+	};
+	let c = bytes.get(..len)
+		.and_then(move |bytes| core::str::from_utf8(bytes).ok())
+		.and_then(move |s| s.chars().next());
+	match c {
+		Some(c) => Some((Some(c), len)),
+		None => Some((None, 1)),
+	}
+}
+
+/// Find `needle` like [`Matcher::find`], by its first byte if it's ASCII, in which case it's `first` or `second`.
+///
+/// Other characters can differ from those they're equal to in every byte, such as `Я` and `я`, so if the first one
+/// isn't ASCII, every window is compared.
+fn find_utf8<M: Matcher<u8>>(matcher: &M, needle: &[u8], haystack: &[u8], first: u8, second: u8) -> Option<usize> {
+	let is_equal = move |window: &[u8]| matcher.is_equal(needle, window);
+	if needle[0].is_ascii() {
+		find_by_first_byte(needle.len(), haystack, first, second, is_equal)
+	} else {
+		haystack.windows(needle.len()).position(is_equal)
+	}
+}
+
+/// Unicode normalization form, for matching text that may be composed differently, behind the `normalization`
+/// feature.
+///
+/// Patterns and haystacks in UTF-8 can be normalized to the same form before they're matched, so that, for example,
+/// `é` as one character matches `e` followed by a combining accent.
+#[cfg(feature = "normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normalization {
+	/// Canonical composition, which most systems use.
+	Nfc,
+	/// Canonical decomposition, which some macOS filesystems use.
+	Nfd,
+}
+
+#[cfg(feature = "normalization")]
+impl Normalization {
+	/// Normalize `bytes` if they're valid UTF-8, borrowing them if they're already normalized or aren't UTF-8.
+	pub fn normalize(self, bytes: &[u8]) -> Cow<'_, [u8]> {
+		let Ok(s) = core::str::from_utf8(bytes) else {
+			return Cow::Borrowed(bytes)
+		};
+		let is_normalized = match self {
+			Self::Nfc => is_nfc_quick(s.chars()),
+			Self::Nfd => is_nfd_quick(s.chars()),
+		};
+		if is_normalized == IsNormalized::Yes {
+			return Cow::Borrowed(bytes)
+		}
+		let normalized: String = match self {
+			Self::Nfc => s.nfc().collect(),
+			Self::Nfd => s.nfd().collect(),
+		};
+		if normalized == s {
+			Cow::Borrowed(bytes)
+		} else {
+			Cow::Owned(normalized.into_bytes())
+		}
+	}
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn unicode_case_insensitive() {
+	let matches = move |a: &str, b: &str| UnicodeCaseInsensitive.is_equal(a.as_bytes(), b.as_bytes());
+	assert!(matches("Карта.bsp", "кАРТА.BSP"));
+	assert!(matches("café.vmt", "CAFÉ.VMT"));
+	assert!(!matches("café.vmt", "cafe.vmt"));
+	assert!(matches("Ærø", "æRØ"));
+	// `ẞ` is longer than `ß` in UTF-8.
+	assert!(!matches("Straße", "STRAẞE"));
+	assert!(!matches("a/b", "a\\b"));
+	assert!(UnicodePathMatch.is_equal("Карта/a".as_bytes(), "кАРТА\\A".as_bytes()));
+	assert!(UnicodeCaseInsensitive.is_equal(b"\xff\xc3", b"\xff\xc3"));
+	assert!(!UnicodeCaseInsensitive.is_equal(b"\xc3\xa9", b"\xc3\xaa"));
+
+	let haystack = "maps/Карта_Café.bsp".as_bytes();
+	for needle in ["карта", "CAFÉ", "_c", "P", ".BSP", "Ка", "рта_"] {
+		let expected = haystack.windows(needle.len())
+			.position(|window| UnicodeCaseInsensitive.is_equal(needle.as_bytes(), window));
+		assert!(expected.is_some(), "{needle}");
+		assert_eq!(UnicodeCaseInsensitive.find(needle.as_bytes(), haystack), expected, "{needle}");
+		assert_eq!(UnicodePathMatch.find(needle.as_bytes(), haystack), expected, "{needle}");
+		for (i, t) in needle.bytes().enumerate() {
+			assert_eq!(UnicodeCaseInsensitive.key(&t), UnicodeCaseInsensitive.key(&haystack[expected.unwrap() + i]));
+		}
+	}
+
+	let chars = move |s: &str| s.chars().collect::<alloc::vec::Vec<_>>();
+	assert!(UnicodeCaseInsensitive.is_equal(&chars("ΣΟΦΊΑ"), &chars("σοφία")));
+	assert!(UnicodeCaseInsensitive.is_in_range(&['а'], &['я'], &['Ж']));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn unicode_any_and_class() {
+	use crate::{
+		Pattern, Syntax,
+		u8_buf::U8Pieces,
+	};
+
+	fn matches(pattern: impl AsRef<[u8]>, haystack: impl AsRef<[u8]>) -> bool {
+		Pattern::<U8Pieces, u8>::parse_with(pattern.as_ref(), &Syntax::<u8>::PATH_GLOB)
+			.first_match(UnicodePathMatch, haystack.as_ref())
+			.is_some()
+	}
+
+	assert!(matches("caf?.vmt", "café.vmt"));
+	assert!(matches("caf?.vmt", "CAFÉ.VMT"));
+	assert!(!matches("caf??.vmt", "café.vmt"));
+	assert!(matches("?/?", "я/Я"));
+	assert!(!matches("?", "/"));
+	assert!(matches("[а-я]*", "привет"));
+	assert!(matches("[а-я]*", "Привет"));
+	assert!(!matches("[а-я]*", "hello"));
+	assert!(matches("[!а-я]*", "hello"));
+	assert!(!matches("[!а-я]*", "привет"));
+	assert!(matches("[éèê]t?", "Été"));
+	assert!(!matches("[a-z]_[A-Z]", "Q_ä"));
+	// Bytes that aren't UTF-8 are matched one at a time.
+	assert!(matches(b"?a", b"\xffa"));
+	assert!(matches(b"??", b"\xc3\x28"));
+	assert!(matches(b"[\xc3][\x28]", b"\xc3\x28"));
+	assert!(matches(b"a[\x80-\xff]", b"a\xfe"));
+	assert_eq!(UnicodePathMatch.element_len("é".as_bytes()), 2);
+	assert_eq!(UnicodePathMatch.element_len(b"\xc3"), 1);
+}
+
+#[cfg(feature = "normalization")]
+#[test]
+fn normalization() {
+	let composed = "caf\u{e9}.vmt".as_bytes();
+	let decomposed = "cafe\u{301}.vmt".as_bytes();
+	assert!(matches!(Normalization::Nfc.normalize(composed), Cow::Borrowed(_)));
+	assert_eq!(Normalization::Nfc.normalize(decomposed), composed);
+	assert_eq!(Normalization::Nfd.normalize(composed), decomposed);
+	assert!(matches!(Normalization::Nfc.normalize(b"\xff"), Cow::Borrowed(_)));
+}
@@ -0,0 +1,230 @@
+use alloc::vec::Vec;
+use core::{
+	fmt,
+	slice,
+};
+
+use crate::{
+	Alternation, Class, Piece, Pieces,
+};
+
+/// Owned [`Pieces`] of any element type, for which [`U8Pieces`](crate::u8_buf::U8Pieces) isn't an option.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct VecPieces<T> {
+	/// Elements of every piece, one after the other. Classes with a range element, and alternations with a separator,
+	/// are followed by it.
+	elements: Vec<T>,
+	entries: Vec<Entry>,
+}
+
+/// Kind of a piece, with the number of its elements in [`VecPieces::elements`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Entry {
+	Literal(usize),
+	Wildcard,
+	Globstar,
+	Any,
+	Class {
+		len: usize,
+		has_range: bool,
+		negated: bool,
+	},
+	Alternation {
+		len: usize,
+		has_separator: bool,
+	},
+}
+
+impl<T> VecPieces<T> {
+	pub const fn new() -> Self {
+		Self {
+			elements: Vec::new(),
+			entries: Vec::new(),
+		}
+	}
+}
+
+impl<T: Clone> VecPieces<T> {
+	/// Push a literal piece, preceded by a wildcard unless it's the first piece.
+	///
+	/// Empty pieces are skipped.
+	pub fn push(&mut self, piece: &[T]) {
+		if !piece.is_empty() {
+			if !self.entries.is_empty() {
+				self.push_piece(Piece::Wildcard);
+			}
+			self.push_piece(Piece::Literal(piece));
+		}
+	}
+
+	pub fn push_piece(&mut self, piece: Piece<'_, T>) {
+		let entry = match piece {
+			Piece::Literal(literal) => {
+				self.elements.extend_from_slice(literal);
+				Entry::Literal(literal.len())
+			}
+			Piece::Wildcard => Entry::Wildcard,
+			Piece::Globstar => Entry::Globstar,
+			Piece::Any => Entry::Any,
+			Piece::Class(class) => {
+				self.elements.extend_from_slice(class.set);
+				self.elements.extend(class.range.cloned());
+				Entry::Class {
+					len: class.set.len(),
+					has_range: class.range.is_some(),
+					negated: class.negated,
+				}
+			}
+			Piece::Alternation(alternation) => {
+				self.elements.extend_from_slice(alternation.alternatives);
+				self.elements.extend(alternation.separator.cloned());
+				Entry::Alternation {
+					len: alternation.alternatives.len(),
+					has_separator: alternation.separator.is_some(),
+				}
+			}
+		};
+		self.entries.push(entry);
+	}
+}
+
+impl<T> Default for VecPieces<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> Pieces<T> for VecPieces<T> {
+	type Iter<'a> = VecPiecesIter<'a, T> where T: 'a;
+	fn pieces(&self) -> Self::Iter<'_> {
+		VecPiecesIter {
+			elements: &self.elements,
+			entries: self.entries.iter(),
+		}
+	}
+}
+
+impl<'a, T: 'a + Clone> FromIterator<&'a [T]> for VecPieces<T> {
+	fn from_iter<I: IntoIterator<Item = &'a [T]>>(iter: I) -> Self {
+		let mut result = Self::new();
+		for piece in iter {
+			result.push(piece);
+		}
+		result
+	}
+}
+
+impl<'a, T: 'a + Clone> FromIterator<Piece<'a, T>> for VecPieces<T> {
+	fn from_iter<I: IntoIterator<Item = Piece<'a, T>>>(iter: I) -> Self {
+		let mut result = Self::new();
+		for piece in iter {
+			result.push_piece(piece);
+		}
+		result
+	}
+}
+
+impl<'a, T: 'a + Clone, A: AsRef<[&'a [T]]>> From<A> for VecPieces<T> {
+	fn from(value: A) -> Self {
+		value.as_ref().iter().copied().collect()
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for VecPieces<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_list().entries(self.pieces()).finish()
+	}
+}
+
+pub struct VecPiecesIter<'a, T> {
+	elements: &'a [T],
+	entries: slice::Iter<'a, Entry>,
+}
+
+impl<T> Clone for VecPiecesIter<'_, T> {
+	fn clone(&self) -> Self {
+		Self {
+			elements: self.elements,
+			entries: self.entries.clone(),
+		}
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for VecPiecesIter<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_list().entries(self.clone()).finish()
+	}
+}
+
+impl<'a, T> Iterator for VecPiecesIter<'a, T> {
+	type Item = Piece<'a, T>;
+	fn next(&mut self) -> Option<Self::Item> {
+		let piece = match *self.entries.next()? {
+			Entry::Literal(len) => {
+				let literal;
+				(literal, self.elements) = self.elements.split_at(len);
+				Piece::Literal(literal)
+			}
+			Entry::Wildcard => Piece::Wildcard,
+			Entry::Globstar => Piece::Globstar,
+			Entry::Any => Piece::Any,
+			Entry::Class { len, has_range, negated } => {
+				let set;
+				(set, self.elements) = self.elements.split_at(len);
+				let range = match self.elements.split_first() {
+					Some((range, rest)) if has_range => {
+						self.elements = rest;
+						Some(range)
+					}
+					_ => None,
+				};
+				Piece::Class(Class { set, range, negated })
+			}
+			Entry::Alternation { len, has_separator } => {
+				let alternatives;
+				(alternatives, self.elements) = self.elements.split_at(len);
+				let separator = match self.elements.split_first() {
+					Some((separator, rest)) if has_separator => {
+						self.elements = rest;
+						Some(separator)
+					}
+					_ => None,
+				};
+				Piece::Alternation(Alternation { alternatives, separator })
+			}
+		};
+		Some(piece)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.entries.size_hint()
+	}
+}
+
+impl<T> ExactSizeIterator for VecPiecesIter<'_, T> {}
+
+#[test]
+fn iter_vec_pieces() {
+	let pieces = VecPieces::from(["one".encode_utf16().collect::<Vec<_>>().as_slice(), &[0x74, 0x77, 0x6f]]);
+	let one = [0x6f, 0x6e, 0x65];
+	assert_eq!(pieces.pieces().collect::<Vec<_>>(), [
+		Piece::Literal(one.as_ref()),
+		Piece::Wildcard,
+		Piece::Literal(&[0x74, 0x77, 0x6f]),
+	]);
+
+	let pieces: VecPieces<char> = [
+		Piece::Globstar,
+		Piece::Class(Class { set: &['a', '-', 'z'], range: Some(&'-'), negated: true }),
+		Piece::Any,
+		Piece::Alternation(Alternation { alternatives: &['a', '|', 'b'], separator: Some(&'|') }),
+		Piece::Literal(&['é']),
+	].into_iter().collect();
+	assert_eq!(pieces.pieces().collect::<Vec<_>>(), [
+		Piece::Globstar,
+		Piece::Class(Class { set: &['a', '-', 'z'], range: Some(&'-'), negated: true }),
+		Piece::Any,
+		Piece::Alternation(Alternation { alternatives: &['a', '|', 'b'], separator: Some(&'|') }),
+		Piece::Literal(&['é']),
+	]);
+}
@@ -0,0 +1,272 @@
+use std::io::{
+	self,
+	Read,
+	Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
+};
+
+use crate::bytes::{
+	le_u32, cstr_at,
+};
+
+const IDENT: &[u8; 4] = b"VBSP";
+const LUMP_COUNT: usize = 64;
+const HEADER_SIZE: u64 = 8 + (LUMP_COUNT as u64) * 16 + 4;
+const STATIC_PROP_NAME_LEN: usize = 128;
+const STATIC_PROP_GAME_LUMP_ID: &[u8; 4] = b"prps";
+
+pub const LUMP_ENTITIES: usize = 0;
+pub const LUMP_GAME_LUMP: usize = 35;
+pub const LUMP_PAKFILE: usize = 40;
+pub const LUMP_TEXDATA_STRING_DATA: usize = 43;
+pub const LUMP_TEXDATA_STRING_TABLE: usize = 44;
+
+/// Position of a lump in a BSP file.
+#[derive(Default, Debug, Clone, Copy)]
+struct LumpInfo {
+	offset: u32,
+	length: u32,
+}
+
+/// Lumps read from a BSP file.
+#[derive(Debug)]
+pub struct Bsp {
+	infos: [LumpInfo; LUMP_COUNT],
+	lumps: [Vec<u8>; LUMP_COUNT],
+}
+
+impl Bsp {
+	/// Read the lumps with the indices in `wanted` from `r`, which must be at the start of a BSP file.
+	///
+	/// Lumps that aren't in `wanted` are left empty. Fails with [`IoErrorKind::Unsupported`] if a wanted lump is
+	/// compressed.
+	pub fn read<R: Read>(mut r: R, wanted: &[usize]) -> IoResult<Self> {
+		let mut header = [0; HEADER_SIZE as usize];
+		r.read_exact(&mut header)?;
+		if &header[..4] != IDENT {
+			return Err(IoError::new(IoErrorKind::InvalidData, "not a BSP file"))
+		}
+
+		let mut infos = [LumpInfo::default(); LUMP_COUNT];
+		for (index, info) in infos.iter_mut().enumerate() {
+			let base = 8 + index * 16;
+			info.offset = le_u32(&header, base).unwrap_or(0);
+			info.length = le_u32(&header, base + 4).unwrap_or(0);
+		}
+
+		let mut order: Vec<usize> = wanted.iter().copied().filter(move |&index| index < LUMP_COUNT).collect();
+		order.sort_by_key(|&index| infos[index].offset);
+		order.dedup();
+
+		let mut lumps: [Vec<u8>; LUMP_COUNT] = std::array::from_fn(move |_| Vec::new());
+		let mut position = HEADER_SIZE;
+		for index in order {
+			let info = infos[index];
+			if info.length == 0 { continue }
+			let offset = u64::from(info.offset);
+			if offset < position {
+				return Err(IoError::new(IoErrorKind::InvalidData, format!("lump {index} overlaps another lump")))
+			}
+			io::copy(&mut (&mut r).take(offset - position), &mut io::sink())?;
+
+			// The length isn't trusted to preallocate, as it may be past the end of the file.
+			let mut lump = Vec::new();
+			(&mut r).take(u64::from(info.length)).read_to_end(&mut lump)?;
+			if lump.len() as u64 != u64::from(info.length) {
+				return Err(IoError::new(IoErrorKind::UnexpectedEof, format!("lump {index} is truncated")))
+			}
+			if lump.starts_with(b"LZMA") {
+				return Err(IoError::new(IoErrorKind::Unsupported, format!("lump {index} is compressed")))
+			}
+			lumps[index] = lump;
+			position = offset + u64::from(info.length);
+		}
+
+		Ok(Self { infos, lumps })
+	}
+
+	/// Get the contents of the lump at `index`, which is empty if it wasn't read.
+	pub fn lump(&self, index: usize) -> &[u8] {
+		&self.lumps[index]
+	}
+
+	/// Parse the entity lump into lists of key-value pairs.
+	pub fn entities(&self) -> Vec<Vec<(String, String)>> {
+		let text = String::from_utf8_lossy(self.lump(LUMP_ENTITIES));
+		let mut entities = Vec::new();
+		let mut tokens = EntityTokens(&text);
+		while let Some(token) = tokens.next() {
+			if token != EntityToken::Open { continue }
+			let mut entity = Vec::new();
+			while let Some(EntityToken::String(key)) = tokens.next() {
+				let Some(EntityToken::String(value)) = tokens.next() else { break };
+				entity.push((key.into(), value.into()));
+			}
+			entities.push(entity);
+		}
+		entities
+	}
+
+	/// Get the names of the materials used by brushes.
+	pub fn texture_names(&self) -> Vec<String> {
+		let data = self.lump(LUMP_TEXDATA_STRING_DATA);
+		self.lump(LUMP_TEXDATA_STRING_TABLE).chunks_exact(4)
+			.filter_map(move |offset| {
+				let offset = u32::from_le_bytes(offset.try_into().ok()?) as usize;
+				Some(cstr_at(data, offset))
+			})
+			.collect()
+	}
+
+	/// Get the names of the models used by static props.
+	pub fn static_prop_models(&self) -> Vec<String> {
+		let lump = self.lump(LUMP_GAME_LUMP);
+		let lump_offset = self.infos[LUMP_GAME_LUMP].offset as usize;
+		let count = le_u32(lump, 0).unwrap_or(0) as usize;
+
+		let mut names = Vec::new();
+		for index in 0..count {
+			let base = 4 + index * 16;
+			let Some(id) = lump.get(base..base + 4) else { break };
+			if id != STATIC_PROP_GAME_LUMP_ID { continue }
+
+			let (Some(offset), Some(length)) = (le_u32(lump, base + 8), le_u32(lump, base + 12)) else { break };
+			// Offsets are usually from the start of the file, but some games make them relative to the lump.
+			let offset = (offset as usize).checked_sub(lump_offset).unwrap_or(offset as usize);
+			let Some(data) = offset.checked_add(length as usize).and_then(|end| lump.get(offset..end)) else { break };
+
+			let dict_count = le_u32(data, 0).unwrap_or(0) as usize;
+			for name in data.get(4..).unwrap_or_default().chunks_exact(STATIC_PROP_NAME_LEN).take(dict_count) {
+				names.push(cstr_at(name, 0));
+			}
+		}
+		names
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum EntityToken<'a> {
+	Open,
+	Close,
+	String(&'a str),
+}
+
+/// Tokenizer for the entity lump.
+struct EntityTokens<'a>(&'a str);
+
+impl<'a> Iterator for EntityTokens<'a> {
+	type Item = EntityToken<'a>;
+	fn next(&mut self) -> Option<Self::Item> {
+		let text = self.0.trim_start();
+		let (token, rest) = match text.chars().next()? {
+			'{' => (EntityToken::Open, &text[1..]),
+			'}' => (EntityToken::Close, &text[1..]),
+			'"' => {
+				let text = &text[1..];
+				let end = text.find('"').unwrap_or(text.len());
+				(EntityToken::String(&text[..end]), text.get(end + 1..).unwrap_or_default())
+			}
+			_ => {
+				let end = text.find(char::is_whitespace).unwrap_or(text.len());
+				(EntityToken::String(&text[..end]), &text[end..])
+			}
+		};
+		self.0 = rest;
+		Some(token)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Build a BSP with `lumps` as `(index, data)`, stored in order after the header.
+	fn build(lumps: &[(usize, &[u8])]) -> Vec<u8> {
+		let mut data = vec![0; HEADER_SIZE as usize];
+		data[..4].copy_from_slice(IDENT);
+		for &(index, lump) in lumps {
+			let offset = data.len() as u32;
+			set_info(&mut data, index, offset, lump.len() as u32);
+			data.extend_from_slice(lump);
+		}
+		data
+	}
+
+	fn set_info(data: &mut [u8], index: usize, offset: u32, length: u32) {
+		let base = 8 + index * 16;
+		data[base..base + 4].copy_from_slice(&offset.to_le_bytes());
+		data[base + 4..base + 8].copy_from_slice(&length.to_le_bytes());
+	}
+
+	#[test]
+	fn reads_wanted_lumps() {
+		let data = build(&[
+			(LUMP_ENTITIES, b"{\n\"classname\" \"worldspawn\"\n}\n{ model \"models/a.mdl\" }"),
+			(LUMP_PAKFILE, b"pakfile"),
+		]);
+		let bsp = Bsp::read(data.as_slice(), &[LUMP_ENTITIES, LUMP_ENTITIES, 100]).unwrap();
+		assert!(bsp.lump(LUMP_PAKFILE).is_empty());
+		assert_eq!(bsp.entities(), [
+			vec![("classname".into(), "worldspawn".into())],
+			vec![("model".into(), "models/a.mdl".into())],
+		]);
+		let bsp = Bsp::read(data.as_slice(), &[LUMP_PAKFILE, LUMP_ENTITIES]).unwrap();
+		assert_eq!(bsp.lump(LUMP_PAKFILE), b"pakfile");
+	}
+
+	#[test]
+	fn reads_texture_names() {
+		let table = [0u32, 6, 100].iter().flat_map(move |offset| offset.to_le_bytes()).collect::<Vec<_>>();
+		let data = build(&[(LUMP_TEXDATA_STRING_DATA, b"brick\0metal"), (LUMP_TEXDATA_STRING_TABLE, &table)]);
+		let bsp = Bsp::read(data.as_slice(), &[LUMP_TEXDATA_STRING_DATA, LUMP_TEXDATA_STRING_TABLE]).unwrap();
+		assert_eq!(bsp.texture_names(), ["brick", "metal", ""]);
+	}
+
+	#[test]
+	fn reads_static_prop_models() {
+		let mut props = 1u32.to_le_bytes().to_vec();
+		let mut name = b"models/a.mdl".to_vec();
+		name.resize(STATIC_PROP_NAME_LEN, 0);
+		props.extend_from_slice(&name);
+
+		let mut lump = 2u32.to_le_bytes().to_vec();
+		for (id, offset, length) in [(b"dprp", 0, 0), (STATIC_PROP_GAME_LUMP_ID, 4 + 2 * 16, props.len())] {
+			lump.extend_from_slice(id);
+			lump.extend_from_slice(&[0; 4]);
+			lump.extend_from_slice(&(offset as u32).to_le_bytes());
+			lump.extend_from_slice(&(length as u32).to_le_bytes());
+		}
+		lump.extend_from_slice(&props);
+		let bsp = Bsp::read(build(&[(LUMP_GAME_LUMP, &lump)]).as_slice(), &[LUMP_GAME_LUMP]).unwrap();
+		assert_eq!(bsp.static_prop_models(), ["models/a.mdl"]);
+
+		// Out of bounds lengths are skipped.
+		let end = lump.len() - props.len() - 4;
+		lump[end..end + 4].copy_from_slice(&0xffffffffu32.to_le_bytes());
+		let bsp = Bsp::read(build(&[(LUMP_GAME_LUMP, &lump)]).as_slice(), &[LUMP_GAME_LUMP]).unwrap();
+		assert!(bsp.static_prop_models().is_empty());
+	}
+
+	#[test]
+	fn rejects_bad_files() {
+		let data = build(&[(LUMP_ENTITIES, b"{}")]);
+		let kind = move |data: &[u8], wanted: &[usize]| Bsp::read(data, wanted).unwrap_err().kind();
+
+		assert_eq!(kind(b"VBSP", &[]), IoErrorKind::UnexpectedEof);
+		let mut other = data.clone();
+		other[..4].copy_from_slice(b"IBSP");
+		assert_eq!(kind(&other, &[]), IoErrorKind::InvalidData);
+
+		// Truncated lumps, including ones with lengths far past the end of the file.
+		assert_eq!(kind(&data[..data.len() - 1], &[LUMP_ENTITIES]), IoErrorKind::UnexpectedEof);
+		let mut huge = data.clone();
+		set_info(&mut huge, LUMP_ENTITIES, HEADER_SIZE as u32, 0xffffffff);
+		assert_eq!(kind(&huge, &[LUMP_ENTITIES]), IoErrorKind::UnexpectedEof);
+
+		let mut overlapping = build(&[(LUMP_ENTITIES, b"{}"), (LUMP_PAKFILE, b"pakfile")]);
+		set_info(&mut overlapping, LUMP_PAKFILE, HEADER_SIZE as u32 + 1, 7);
+		assert_eq!(kind(&overlapping, &[LUMP_ENTITIES, LUMP_PAKFILE]), IoErrorKind::InvalidData);
+
+		let compressed = build(&[(LUMP_ENTITIES, b"LZMA")]);
+		assert_eq!(kind(&compressed, &[LUMP_ENTITIES]), IoErrorKind::Unsupported);
+	}
+}
@@ -0,0 +1,56 @@
+/// Read a little-endian [`u16`] at `offset` in `data`.
+pub fn le_u16(data: &[u8], offset: usize) -> Option<u16> {
+	data.get(offset..offset.checked_add(2)?)
+		.and_then(move |bytes| bytes.try_into().ok())
+		.map(u16::from_le_bytes)
+}
+
+/// Read a little-endian [`u32`] at `offset` in `data`.
+pub fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+	data.get(offset..offset.checked_add(4)?)
+		.and_then(move |bytes| bytes.try_into().ok())
+		.map(u32::from_le_bytes)
+}
+
+/// Read a null-terminated string at `offset` in `data`, replacing invalid UTF-8.
+pub fn cstr_at(data: &[u8], offset: usize) -> String {
+	let bytes = data.get(offset..).unwrap_or_default();
+	let bytes = bytes.split(move |&b| b == 0).next().unwrap_or_default();
+	String::from_utf8_lossy(bytes).into_owned()
+}
+
+const CRC32_TABLE: [u32; 256] = {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+};
+
+/// CRC-32 checksum, as used by ZIP and VPK files.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+	pub const fn new() -> Self {
+		Self(!0)
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		for &b in data {
+			self.0 = CRC32_TABLE[((self.0 ^ u32::from(b)) & 0xff) as usize] ^ (self.0 >> 8);
+		}
+	}
+
+	pub const fn finish(self) -> u32 {
+		!self.0
+	}
+}
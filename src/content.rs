@@ -0,0 +1,281 @@
+use std::io::{
+	Read, Result as IoResult,
+};
+
+use crate::{
+	bsp::{
+		self, Bsp,
+	},
+	keyvalues::{
+		self, Value,
+	},
+	mdl,
+};
+
+/// Characters that may prefix a sound path to change how it's played.
+const SOUND_CHARS: &[char] = &['*', '#', '@', '>', '<', '^', ')', '(', '}', '$', '!', '?', '&', '~', '`', '+', '%'];
+
+/// Suffixes of the files that accompany a map, appended to the path of the map without `.bsp`.
+const MAP_COMPANION_SUFFIXES: &[&str] = &[".nav", ".res", ".txt", "_particles.txt", "_level_sounds.txt"];
+
+/// Suffixes of the materials that make up a skybox, appended to the sky name.
+const SKYBOX_SIDES: &[&str] = &["bk", "dn", "ft", "lf", "rt", "up"];
+
+/// Material parameters whose values name textures.
+const TEXTURE_PARAMETERS: &[&str] = &[
+	"$basetexture", "$basetexture2", "$basetexture3", "$basetexture4", "$hdrbasetexture", "$hdrcompressedtexture",
+	"$bumpmap", "$bumpmap2", "$normalmap", "$normalmap2", "$parallaxmap", "$heightmap",
+	"$detail", "$detail1", "$detail2", "$envmap", "$envmapmask", "$envmapmask2",
+	"$phongexponenttexture", "$phongwarptexture", "$lightwarptexture", "$selfillummask", "$selfillumtexture",
+	"$blendmodulatetexture", "$tintmasktexture", "$ambientoccltexture", "$ambientocclusiontexture",
+	"$iris", "$corneatexture", "$fresnelrangestexture", "$dudvmap", "$refracttexture", "$reflecttexture",
+	"$flowmap", "$flow_noise_texture", "$texture2", "$masks1", "$masks2", "$sheenmapmask",
+	"$emissiveblendtexture", "$emissiveblendbasetexture", "$emissiveblendflowtexture",
+];
+
+/// Material parameters whose values name other materials, including the material that a `patch` material
+/// includes.
+const MATERIAL_PARAMETERS: &[&str] = &["include", "$bottommaterial", "$underwateroverlay", "$fallbackmaterial"];
+
+/// Lumps that are needed to find the content referred to by a map.
+const MAP_LUMPS: &[usize] = &[
+	bsp::LUMP_ENTITIES, bsp::LUMP_GAME_LUMP, bsp::LUMP_TEXDATA_STRING_DATA, bsp::LUMP_TEXDATA_STRING_TABLE,
+];
+
+/// Normalize a path to content, so that it can be compared to other normalized paths.
+pub fn normalize_path(path: &str) -> String {
+	let mut normalized = String::with_capacity(path.len());
+	for component in path.trim().split(['/', '\\']).filter(move |component| !component.is_empty()) {
+		if !normalized.is_empty() {
+			normalized.push('/');
+		}
+		normalized.push_str(&component.to_ascii_lowercase());
+	}
+	normalized
+}
+
+/// Returns `true` if the normalized `path` is that of a map that the game can load.
+pub fn is_map(path: &str) -> bool {
+	path.strip_prefix("maps/").is_some_and(move |name| name.ends_with(".bsp") && !name.contains('/'))
+}
+
+/// Returns `true` if the file at the normalized `path` may refer to other content.
+pub fn has_references(path: &str) -> bool {
+	path.ends_with(".bsp") || path.ends_with(".mdl") || path.ends_with(".vmt")
+}
+
+/// Get the normalized paths of the content that the file at the normalized `path` refers to, reading its contents
+/// from `r`.
+///
+/// The returned paths may refer to content that doesn't exist, such as content shipped with the game.
+pub fn references<R: Read>(path: &str, mut r: R) -> IoResult<Vec<String>> {
+	if path.ends_with(".bsp") {
+		let bsp = Bsp::read(r, MAP_LUMPS)?;
+		Ok(map_references(path, &bsp))
+	} else if path.ends_with(".mdl") {
+		let mut data = Vec::new();
+		r.read_to_end(&mut data)?;
+		model_references(path, &data)
+	} else if path.ends_with(".vmt") {
+		let mut data = Vec::new();
+		r.read_to_end(&mut data)?;
+		Ok(material_references(&data))
+	} else {
+		Ok(Vec::new())
+	}
+}
+
+/// Get the normalized paths of the files that accompany the file at the normalized `path`, along with whether
+/// each of them is required.
+pub fn companions(path: &str) -> Vec<(String, bool)> {
+	let Some(stem) = path.strip_suffix(".mdl") else {
+		return Vec::new()
+	};
+	mdl::COMPANION_EXTENSIONS.iter()
+		.map(move |extension| (
+			format!("{stem}.{extension}"),
+			mdl::REQUIRED_COMPANION_EXTENSIONS.contains(extension),
+		))
+		.collect()
+}
+
+/// Returns `true` if the file at the normalized `path` may depend on other content.
+pub fn has_dependencies(path: &str) -> bool {
+	path.ends_with(".vmt")
+}
+
+/// Get the normalized paths of the content that must be present for the file at the normalized `path` to work,
+/// reading its contents from `r`.
+pub fn dependencies<R: Read>(path: &str, mut r: R) -> IoResult<Vec<String>> {
+	if path.ends_with(".vmt") {
+		let mut data = Vec::new();
+		r.read_to_end(&mut data)?;
+		Ok(material_references(&data))
+	} else {
+		Ok(Vec::new())
+	}
+}
+
+fn map_references(map_path: &str, bsp: &Bsp) -> Vec<String> {
+	let mut references = Vec::new();
+
+	if let Some(stem) = map_path.strip_suffix(".bsp") {
+		for suffix in MAP_COMPANION_SUFFIXES {
+			references.push(format!("{stem}{suffix}"));
+		}
+	}
+
+	for name in bsp.texture_names() {
+		references.push(material_path(&name));
+	}
+
+	for model in bsp.static_prop_models() {
+		references.push(normalize_path(&model));
+	}
+
+	for entity in bsp.entities() {
+		for (key, value) in entity {
+			if key.eq_ignore_ascii_case("skyname") {
+				for side in SKYBOX_SIDES {
+					references.push(material_path(&format!("skybox/{value}{side}")));
+				}
+			} else if let Some(path) = entity_value_path(&value) {
+				references.push(path);
+			}
+		}
+	}
+
+	references
+}
+
+fn model_references(model_path: &str, data: &[u8]) -> IoResult<Vec<String>> {
+	let stem = model_path.strip_suffix(".mdl").unwrap_or(model_path);
+	let mut references: Vec<String> = mdl::COMPANION_EXTENSIONS.iter()
+		.map(move |extension| format!("{stem}.{extension}"))
+		.collect();
+	references.extend(mdl::material_names(data)?.iter().map(move |name| material_path(name)));
+	Ok(references)
+}
+
+fn material_references(data: &[u8]) -> Vec<String> {
+	fn push_references(pairs: &[(String, Value)], references: &mut Vec<String>) {
+		for (key, value) in pairs {
+			let value = match value {
+				Value::Block(pairs) => {
+					push_references(pairs, references);
+					continue
+				}
+				Value::String(value) => value,
+			};
+
+			let key = key.to_ascii_lowercase();
+			if TEXTURE_PARAMETERS.contains(&key.as_str()) {
+				references.extend(texture_path(value));
+			} else if MATERIAL_PARAMETERS.contains(&key.as_str()) && !value.trim().is_empty() {
+				references.push(material_path(value));
+			}
+		}
+	}
+
+	let mut references = Vec::new();
+	push_references(&keyvalues::parse(&String::from_utf8_lossy(data)), &mut references);
+	references
+}
+
+/// Get the normalized path of the `.vmt` file for the material `name`.
+fn material_path(name: &str) -> String {
+	let name = normalize_path(name);
+	let name = name.strip_prefix("materials/").unwrap_or(&name);
+	let name = name.strip_suffix(".vmt").unwrap_or(name);
+	format!("materials/{name}.vmt")
+}
+
+/// Get the normalized path of the `.vtf` file for the texture `name`, unless it names a texture that the game
+/// generates.
+fn texture_path(name: &str) -> Option<String> {
+	let name = normalize_path(name);
+	let name = name.strip_prefix("materials/").unwrap_or(&name);
+	let name = name.strip_suffix(".vtf").unwrap_or(name);
+	if name.is_empty() || name.starts_with("_rt_") || name == "env_cubemap" {
+		return None
+	}
+	Some(format!("materials/{name}.vtf"))
+}
+
+/// Get the normalized path of the content that an entity key value refers to, if it looks like a path.
+fn entity_value_path(value: &str) -> Option<String> {
+	let value = normalize_path(value);
+	let (stem, extension) = value.rsplit_once('.')?;
+	match extension {
+		"mdl" | "pcf" => Some(value),
+		"vmt" => Some(material_path(&value)),
+		"spr" => Some(material_path(stem)),
+		"vtf" => texture_path(&value),
+		"wav" | "mp3" | "ogg" => Some(in_dir("sound/", value.trim_start_matches(SOUND_CHARS))),
+		_ => None,
+	}
+}
+
+fn in_dir(dir: &str, path: &str) -> String {
+	if path.starts_with(dir) {
+		path.into()
+	} else {
+		format!("{dir}{path}")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalizes_paths() {
+		assert_eq!(normalize_path(" Materials\\Brick//Wall.VMT "), "materials/brick/wall.vmt");
+		assert_eq!(normalize_path("/maps/"), "maps");
+		assert!(is_map("maps/de_dust.bsp"));
+		assert!(!is_map("maps/graphs/de_dust.bsp"));
+		assert!(!is_map("de_dust.bsp"));
+	}
+
+	#[test]
+	fn lists_model_companions() {
+		let companions = companions("models/props/crate.mdl");
+		assert_eq!(companions.len(), mdl::COMPANION_EXTENSIONS.len());
+		assert!(companions.contains(&("models/props/crate.vvd".into(), true)));
+		assert!(companions.contains(&("models/props/crate.dx90.vtx".into(), true)));
+		assert!(companions.contains(&("models/props/crate.phy".into(), false)));
+		assert!(super::companions("materials/brick/wall.vmt").is_empty());
+	}
+
+	#[test]
+	fn lists_material_references() {
+		let vmt = "\
+patch
+{
+	include \"materials/brick/base.vmt\"
+	replace
+	{
+		$BaseTexture \"Brick\\Wall.vtf\"
+		$bumpmap brick/wall_normal
+		$envmap env_cubemap
+		$detail _rt_camera
+		$fallbackmaterial \" \"
+	}
+}";
+		assert_eq!(references("materials/brick/wall.vmt", vmt.as_bytes()).unwrap(), [
+			"materials/brick/base.vmt", "materials/brick/wall.vtf", "materials/brick/wall_normal.vtf",
+		]);
+		assert_eq!(dependencies("materials/brick/wall.vmt", vmt.as_bytes()).unwrap().len(), 3);
+		assert!(references("sound/a.wav", &b"$basetexture a"[..]).unwrap().is_empty());
+	}
+
+	#[test]
+	fn finds_entity_value_paths() {
+		assert_eq!(entity_value_path("Models/Props/Crate.mdl").as_deref(), Some("models/props/crate.mdl"));
+		assert_eq!(entity_value_path("sprites/glow.spr").as_deref(), Some("materials/sprites/glow.vmt"));
+		assert_eq!(entity_value_path("#music/theme.mp3").as_deref(), Some("sound/music/theme.mp3"));
+		assert_eq!(entity_value_path("sound/ambient/wind.wav").as_deref(), Some("sound/ambient/wind.wav"));
+		assert_eq!(entity_value_path("_rt_camera.vtf"), None);
+		assert_eq!(entity_value_path("info_player_start"), None);
+	}
+}
@@ -0,0 +1,140 @@
+use std::{
+	fmt,
+	iter::Peekable,
+	vec,
+};
+
+use crate::{
+	Expr, Pattern,
+	trim_pattern_line,
+};
+
+/// Parse a grouped expression of patterns, such as `(materials/** & *.vtf & !materials/dev/**)`, parsing each
+/// pattern with `parse_pattern`.
+///
+/// `&` binds tighter than `|`, and `!` negates the pattern or group after it. Patterns extend up to the next
+/// unescaped `(`, `)`, `&` or `|`, and are trimmed of whitespace.
+pub fn parse_expression(expression: &str, parse_pattern: impl Fn(&str) -> Pattern) -> Result<Expr, String> {
+	let mut parser = Parser {
+		tokens: tokenize(expression).into_iter().peekable(),
+		parse_pattern,
+	};
+	let expr = parser.or()?;
+	match parser.tokens.next() {
+		None => Ok(expr),
+		Some(token) => Err(format!("unexpected {token}")),
+	}
+}
+
+/// Returns `true` if `expr` negates a pattern or group, so that it may not match paths in a directory that it matches.
+pub fn has_negation(expr: &Expr) -> bool {
+	match expr {
+		Expr::Pattern(_) => false,
+		Expr::All(exprs) | Expr::Any(exprs) => exprs.iter().any(has_negation),
+		Expr::Not(_) => true,
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+	Open,
+	Close,
+	And,
+	Or,
+	Not,
+	Pattern(&'a str),
+}
+
+impl fmt::Display for Token<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Open => f.write_str("`(`"),
+			Self::Close => f.write_str("`)`"),
+			Self::And => f.write_str("`&`"),
+			Self::Or => f.write_str("`|`"),
+			Self::Not => f.write_str("`!`"),
+			Self::Pattern(pattern) => write!(f, "pattern `{pattern}`"),
+		}
+	}
+}
+
+fn tokenize(mut expression: &str) -> Vec<Token<'_>> {
+	let mut tokens = Vec::new();
+	loop {
+		expression = expression.trim_start();
+		let Some(first) = expression.chars().next() else {
+			break
+		};
+		let token = match first {
+			'(' => Token::Open,
+			')' => Token::Close,
+			'&' => Token::And,
+			'|' => Token::Or,
+			// Only a `!` at the start of an operand negates it, so that patterns can contain it.
+			'!' => Token::Not,
+			_ => {
+				let (pattern, rest) = expression.split_at(pattern_len(expression));
+				tokens.push(Token::Pattern(trim_pattern_line(pattern)));
+				expression = rest;
+				continue
+			}
+		};
+		tokens.push(token);
+		expression = &expression[1..];
+	}
+	tokens
+}
+
+/// Get the length of the pattern at the start of `expression`, up to the next unescaped operator.
+fn pattern_len(expression: &str) -> usize {
+	let mut is_escaped = false;
+	for (i, c) in expression.char_indices() {
+		match c {
+			_ if is_escaped => is_escaped = false,
+			'\\' => is_escaped = true,
+			'(' | ')' | '&' | '|' => return i,
+			_ => {}
+		}
+	}
+	expression.len()
+}
+
+struct Parser<'a, F> {
+	tokens: Peekable<vec::IntoIter<Token<'a>>>,
+	parse_pattern: F,
+}
+
+impl<F: Fn(&str) -> Pattern> Parser<'_, F> {
+	fn or(&mut self) -> Result<Expr, String> {
+		let mut operands = vec![self.and()?];
+		while self.tokens.next_if_eq(&Token::Or).is_some() {
+			operands.push(self.and()?);
+		}
+		Ok(if operands.len() == 1 { operands.remove(0) } else { Expr::Any(operands) })
+	}
+
+	fn and(&mut self) -> Result<Expr, String> {
+		let mut operands = vec![self.unary()?];
+		while self.tokens.next_if_eq(&Token::And).is_some() {
+			operands.push(self.unary()?);
+		}
+		Ok(if operands.len() == 1 { operands.remove(0) } else { Expr::All(operands) })
+	}
+
+	fn unary(&mut self) -> Result<Expr, String> {
+		match self.tokens.next() {
+			Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+			Some(Token::Open) => {
+				let expr = self.or()?;
+				match self.tokens.next() {
+					Some(Token::Close) => Ok(expr),
+					Some(token) => Err(format!("expected `)`, found {token}")),
+					None => Err("expected `)`, found end of line".into()),
+				}
+			}
+			Some(Token::Pattern(pattern)) => Ok(Expr::Pattern((self.parse_pattern)(pattern))),
+			Some(token) => Err(format!("expected pattern, `!` or `(`, found {token}")),
+			None => Err("expected pattern, `!` or `(`, found end of line".into()),
+		}
+	}
+}
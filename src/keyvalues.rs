@@ -0,0 +1,116 @@
+/// Value of a key in a KeyValues document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+	String(String),
+	Block(Vec<(String, Value)>),
+}
+
+/// Parse the KeyValues document `text`, such as a `.vmt` file, into its top-level key-value pairs.
+///
+/// Parsing is lenient, like the game's: comments and conditionals like `[$WIN32]` are skipped, and anything after
+/// an unbalanced `}` or a dangling key is ignored.
+pub fn parse(text: &str) -> Vec<(String, Value)> {
+	parse_block(&mut Tokens(text))
+}
+
+fn parse_block(tokens: &mut Tokens<'_>) -> Vec<(String, Value)> {
+	let mut pairs = Vec::new();
+	while let Some(Token::String(key)) = tokens.next() {
+		let value = match tokens.next() {
+			Some(Token::String(value)) => Value::String(value.into()),
+			Some(Token::Open) => Value::Block(parse_block(tokens)),
+			_ => break,
+		};
+		pairs.push((key.into(), value));
+	}
+	pairs
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+	Open,
+	Close,
+	String(&'a str),
+}
+
+/// Tokenizer for KeyValues text that skips comments and conditionals.
+struct Tokens<'a>(&'a str);
+
+impl<'a> Tokens<'a> {
+	fn next_any(&mut self) -> Option<(Token<'a>, bool)> {
+		let text = loop {
+			let text = self.0.trim_start();
+			match text.strip_prefix("//") {
+				Some(comment) => self.0 = comment.split_once('\n').map(move |(_, rest)| rest).unwrap_or_default(),
+				None => break text,
+			}
+		};
+
+		let (token, is_quoted, rest) = match text.chars().next()? {
+			'{' => (Token::Open, false, &text[1..]),
+			'}' => (Token::Close, false, &text[1..]),
+			'"' => {
+				let text = &text[1..];
+				let end = text.find('"').unwrap_or(text.len());
+				(Token::String(&text[..end]), true, text.get(end + 1..).unwrap_or_default())
+			}
+			_ => {
+				let end = text.find(move |c: char| c.is_whitespace() || matches!(c, '{' | '}' | '"'))
+					.unwrap_or(text.len());
+				(Token::String(&text[..end]), false, &text[end..])
+			}
+		};
+		self.0 = rest;
+		Some((token, is_quoted))
+	}
+}
+
+impl<'a> Iterator for Tokens<'a> {
+	type Item = Token<'a>;
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			match self.next_any()? {
+				(Token::String(s), false) if s.starts_with('[') && s.ends_with(']') => continue,
+				(token, _) => return Some(token),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn string(key: &str, value: &str) -> (String, Value) {
+		(key.into(), Value::String(value.into()))
+	}
+
+	#[test]
+	fn parses_blocks() {
+		let text = "\
+\"LightmappedGeneric\" // Comment
+{
+	$basetexture \"brick/wall\" [$WIN32]
+	\"$bumpmap\" brick/wall_normal
+	Proxies { AnimatedTexture { animatedtexturevar $basetexture } }
+}";
+		assert_eq!(parse(text), [("LightmappedGeneric".into(), Value::Block(vec![
+			string("$basetexture", "brick/wall"),
+			string("$bumpmap", "brick/wall_normal"),
+			("Proxies".into(), Value::Block(vec![
+				("AnimatedTexture".into(), Value::Block(vec![string("animatedtexturevar", "$basetexture")])),
+			])),
+		]))]);
+		assert_eq!(parse("\"[$X360]\" \"quoted\""), [string("[$X360]", "quoted")]);
+	}
+
+	#[test]
+	fn malformed_text_is_cut_short() {
+		assert_eq!(parse("a b } c d"), [string("a", "b")]);
+		assert_eq!(parse("a b c"), [string("a", "b")]);
+		assert_eq!(parse("a { b c"), [("a".into(), Value::Block(vec![string("b", "c")]))]);
+		assert_eq!(parse("a \"unterminated"), [string("a", "unterminated")]);
+		assert_eq!(parse("{ a b }"), []);
+		assert_eq!(parse("// only a comment"), []);
+	}
+}
@@ -4,23 +4,28 @@ use bzip2::{
 };
 use pico_args::Arguments;
 use rustc_hash::{
-	FxHashMap, FxBuildHasher,
+	FxHashMap, FxHashSet,
 };
 use slicepat::{
-	PathMatch,
+	Condition, ExactPathMatch, KeyMatcher, Matcher, PatternSet, RuleSet, Syntax,
 	u8_buf::U8Pieces,
 };
 use std::{
 	borrow::Cow,
 	env::args_os,
+	fmt,
 	fs::{
 		create_dir_all, File,
 	},
 	hash::Hash,
+	mem::{
+		replace, take,
+	},
 	io::{
+		self,
 		Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
 		BufReader,
-		Read, Write, BufRead,
+		Read, BufRead,
 	},
 	ops::{
 		Deref, DerefMut,
@@ -29,6 +34,7 @@ use std::{
 		Path, PathBuf
 	},
 	process::ExitCode,
+	rc::Rc,
 	time::SystemTime,
 };
 use tokio::{
@@ -39,7 +45,48 @@ use tokio::{
 mod state;
 use state::*;
 
+mod vpk;
+use vpk::Vpk;
+
+mod bsp;
+use bsp::Bsp;
+
+mod bytes;
+use bytes::Crc32;
+
+mod content;
+mod keyvalues;
+mod mdl;
+mod zip;
+
+mod rewrite;
+use rewrite::RewriteRules;
+
+mod expression;
+use expression::{has_negation, parse_expression};
+
 type Pattern = slicepat::Pattern<U8Pieces, u8>;
+type Expr = slicepat::Expr<U8Pieces, u8>;
+
+// Matcher for paths that ignores letter case, of every letter with the `unicode` feature, or of ASCII letters
+// otherwise.
+#[cfg(feature = "unicode")]
+use slicepat::UnicodePathMatch as CaseInsensitivePathMatch;
+#[cfg(not(feature = "unicode"))]
+use slicepat::PathMatch as CaseInsensitivePathMatch;
+
+/// Normalize `bytes` to NFC with the `unicode` feature, so that accents match whether they're composed with their
+/// letters or not.
+fn normalize(bytes: &[u8]) -> Cow<'_, [u8]> {
+	#[cfg(feature = "unicode")]
+	{
+		slicepat::Normalization::Nfc.normalize(bytes)
+	}
+	#[cfg(not(feature = "unicode"))]
+	{
+		Cow::Borrowed(bytes)
+	}
+}
 
 fn main() -> ExitCode {
 	macro_rules! err_or_return {
@@ -67,6 +114,7 @@ fn main() -> ExitCode {
 
 --from <path>:
 	Source directory, with uncompressed files.
+	May also be a VPK, in which case its entries are compressed.
 --to <path>:
 	Destination directory, to be filled with compressed files.
 --state <path>:
@@ -79,11 +127,111 @@ fn main() -> ExitCode {
 
 	Each pattern is defined on a separate line, with a `*` symbol
 	denoting that any character before the sequence after it is
-	accepted.
+	accepted, except for `/`, a `?` symbol denoting any single
+	character, and `[...]` denoting any single character in the
+	brackets, such as `[a-z]`, or not in them, such as `[!0-9]`.
+	`{{...}}` denotes any one of the comma-separated names in the
+	braces, such as in `materials/*.{{vmt,vtf}}`.
+	A `\\` symbol makes the character after it literal, such as in
+	`\\*`, `\\#`, `\\!`, or `\\ `, which keeps a space that would
+	otherwise be trimmed.
+	A `**` segment, such as in `maps/**/*.bsp`, denotes any number
+	of directories.
+	As in `.gitignore` files, patterns containing a `/` before their
+	end match paths from the source directory, while other patterns
+	match files and directories at any depth. Patterns ending with
+	`/` only match directories.
 	Lines, trimmed of whitespace, beginning with `#`, denote
 	comments.
 	Patterns beginning with `!` match files that are to always be
-	included.
+	included, unless a later pattern matches them. Later patterns
+	override earlier ones. They also apply in ignored directories,
+	so that `sound/` followed by `!sound/custom/intro.wav` only
+	includes that file from `sound`.
+	Lines beginning with `expr `, or `!expr `, are expressions that
+	combine patterns with `&` (and), `|` (or), `!` (not) and
+	parentheses, such as
+	`expr materials/** & *.vtf & !materials/dev/**`. `&` binds
+	tighter than `|`, and a `\\` makes `(`, `)`, `&` and `|` in their
+	patterns literal, such as in `expr \\(1\\).bsp | *.nav`.
+	Expressions with a `!` are only matched against files, so they
+	don't ignore whole directories.
+	Letter case is ignored. If built with the `unicode` feature,
+	that includes names such as `Карта.bsp`, and whether accents
+	are composed with their letters is ignored too.
+	The line `case sensitive` makes the patterns after it match
+	letter case exactly, until the line `case insensitive`.
+	The line `include <path>` reads the rules of another ignore
+	file in its place, with `<path>` relative to the directory of
+	the file that includes it. Files can't include themselves,
+	directly or through others.
+
+	Files named `.fastdlignore` in the source directory and its
+	subdirectories are read as well, with patterns matching paths
+	from the directory that they're in. Their rules override those
+	of the ignore files in the directories containing them, and of
+	this file. They're never compressed.
+--exclude <pattern>:
+	May be given multiple times.
+	Pattern for source file paths that must not be compressed, as
+	in the ignore file, but without a leading `!`. Overrides the
+	ignore file.
+--include <pattern>:
+	May be given multiple times.
+	Pattern for source file paths that are to always be
+	compressed, like a pattern beginning with `!` in the ignore
+	file. Overrides the ignore file and `--exclude`.
+--rewrite <path>:
+	Path to file containing rules that rewrite the paths of source
+	files into the paths that they're served from.
+
+	Each rule is defined on a separate line as
+	`<pattern> -> <template>`, with patterns written as in the
+	ignore file, but always matching paths from the source
+	directory. A `\\` makes a `->` in a pattern literal, as in
+	`\\->`. Each wildcard in the template is replaced by what
+	the wildcard at the same position in the pattern matched, and
+	`$N` is replaced by what the `N`th wildcard matched, with each
+	`**` counting as one wildcard, so that `custom/*/** -> $2`
+	strips `custom/<addon>/`.
+	Rules are applied in order, each to the path rewritten by the
+	previous ones.
+	The line `lowercase` makes all rewritten paths lowercase.
+	Lines, trimmed of whitespace, beginning with `#`, denote
+	comments.
+	Fails if two files are rewritten to the same path.
+--expand-vpks:
+	Compress the entries of VPKs found in the source directory,
+	instead of the VPKs themselves.
+	Entries are placed at the root of the destination directory,
+	as the game mounts them, and ignore patterns, including those
+	of `.fastdlignore` files in the directories containing the VPK,
+	are matched against their paths inside the VPK.
+--maps-first:
+	Only compress the maps in the `maps` directory, and the
+	materials, models, sounds and other files that they refer to,
+	directly or through other content.
+	Files that aren't referred to are listed, prefixed with `?`,
+	and are not compressed.
+	Maps with compressed lumps are listed, and compressed, but the
+	content that they refer to isn't found.
+--check-pakfiles:
+	Instead of compressing, list the files packed into every map
+	in the source directory.
+	Packed files that are also present as loose files are
+	followed by ` = <path>` if they're the same, which wastes a
+	download, or ` != <path>` if they differ, which causes
+	mismatches between clients.
+	Fails if any packed file differs from its loose file.
+--check-dependencies:
+	Before compressing, list the textures and materials that
+	materials to be compressed depend on, and the companion files
+	(`.vvd`, `.vtx`, `.phy` and such) of models to be compressed,
+	which are missing from the source directory or are ignored.
+	Optional companion files are only listed if they're ignored.
+--strict:
+	Fail instead of compressing if `--check-dependencies` finds
+	any problems.
 --level <compression level>:
 	Defaults to `--level best`.
 	Bzip2 compression level. Can be one of:
@@ -116,12 +264,6 @@ fn main() -> ExitCode {
 
 	err_or_return!(state.read_all(); e => "Couldn't read from state file {state_path:?}: {e}");
 
-	let mut to_compress = Vec::new();
-	struct ToCompress {
-		pub source_path: PathBuf,
-		pub destination_path: PathBuf,
-	}
-
 	let compression = {
 		let level: Cow<'static, str> = err_or_return!(args.opt_value_from_str("--level"))
 			.map(Cow::Owned)
@@ -150,79 +292,119 @@ fn main() -> ExitCode {
 	let ignore_patterns = {
 		let mut map = PatternMap::new();
 		if let Some(path) = err_or_return!(args.opt_value_from_str::<_, PathBuf>("--ignore")) {
-			match File::open(path) {
-				Ok(f) => {
-					err_or_return!(
-						map.read_from(BufReader::new(f));
-						e => "Failed to read ignore file: {e}"
-					);
-				}
+			match map.read_file(&path) {
+				Ok(()) => {}
 				Err(e) if e.kind() == IoErrorKind::NotFound => {}
 				Err(e) => {
-					eprintln!("Failed to open ignore file: {e}");
+					eprintln!("Failed to read ignore file: {e}");
 					return ExitCode::FAILURE
 				}
 			};
 		}
+		// Patterns from the command line override those of the file, and included ones override excluded ones.
+		let excluded: Vec<String> = err_or_return!(args.values_from_str("--exclude"));
+		let included: Vec<String> = err_or_return!(args.values_from_str("--include"));
+		let cli_patterns = excluded.iter().map(move |pattern| (pattern.as_str(), Directive::Include))
+			.chain(included.iter().map(move |pattern| (pattern.as_str(), Directive::Exclude)));
+		err_or_return!(map.push_patterns(cli_patterns); e => "Invalid ignore pattern {e}");
 		map
 	};
 
-	let mut to_traverse = vec![source_dir.clone()];
-	while let Some(dir) = to_traverse.pop() {
-		let items = err_or_return!(dir.read_dir(); e => "Couldn't read directory {dir:?}: {e}");
+	let rewrite_rules = match err_or_return!(args.opt_value_from_str::<_, PathBuf>("--rewrite")) {
+		Some(path) => {
+			let file = err_or_return!(File::open(&path); e => "Failed to open rewrite file: {e}");
+			err_or_return!(RewriteRules::read_from(BufReader::new(file)); e => "Failed to read rewrite file: {e}")
+		}
+		None => RewriteRules::default(),
+	};
 
-		for item in items.flatten() {
-			let source_path = item.path();
-			let relative_path = source_path.strip_prefix(&source_dir)
-				.expect("`item.path()` returns with prefix of `dir`");
+	let expand_vpks = args.contains("--expand-vpks");
+	let maps_first = args.contains("--maps-first");
+	let check_pakfiles = args.contains("--check-pakfiles");
+	let check_dependencies = args.contains("--check-dependencies");
+	let strict = args.contains("--strict");
 
-			if ignore_patterns.has_match(relative_path.as_os_str().as_encoded_bytes()) {
-				println!("!{}", source_path.display());
-				continue
-			}
+	let root_scope = Rc::new(IgnoreScope {
+		dir: source_dir.clone(),
+		patterns: ignore_patterns,
+		parent: None,
+	});
 
-			let metadata = err_or_return!(item.metadata(); e => "Couldn't get metadata for {source_path:?}: {e}");
+	let mut tree = Tree::default();
+	if source_dir.is_file() {
+		err_or_return!(
+			push_vpk_candidates(&source_dir, Path::new(""), &root_scope, &mut tree);
+			e => "Couldn't read VPK {source_dir:?}: {e}"
+		);
+	}
 
-			if metadata.is_dir() {
-				to_traverse.push(source_path);
-			} else {
-				let fs_time = metadata.modified()
-					.expect("last modification time should be supported")
-					.duration_since(SystemTime::UNIX_EPOCH)
-					.expect("system clock should be past the Unix epoch")
-					.as_secs();
+	if source_dir.is_dir() {
+		err_or_return!(push_dir_candidates(&source_dir, root_scope, expand_vpks, &mut tree));
+	}
 
-				let mut destination_path = dest_dir.join(relative_path);
-				if let Some(extension) = destination_path.extension() {
-					let mut extension = extension.to_os_string();
-					extension.push(".bz2");
-					destination_path.set_extension(extension);
-				} else {
-					destination_path.set_extension("bz2");
-				}
+	rewrite_candidates(&mut tree.candidates, &rewrite_rules);
+	if report_collisions(&tree.candidates) {
+		return ExitCode::FAILURE
+	}
 
-				if let Some(parent_path) = destination_path.parent() {
-					err_or_return!(
-						create_dir_all(parent_path);
-						e => "Couldn't create parent directories for {destination_path:?}: {e}"
-					);
-				}
+	if check_pakfiles {
+		let has_conflicts = err_or_return!(check_pakfiles_of(&tree.candidates); e => "Couldn't check pakfiles: {e}");
+		return if !has_conflicts { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+	}
 
-				if
-					!destination_path.exists()
-					|| state.time_of(relative_path) != Some(fs_time)
-				{
-					err_or_return!(
-						state.set_time_of(relative_path, fs_time);
-						e => "Couldn't write time for {source_path:?}: {e}"
-					);
-
-					to_compress.push(ToCompress {
-						source_path,
-						destination_path,
-					});
-				}
-			}
+	if maps_first {
+		err_or_return!(
+			retain_referenced(&mut tree.candidates);
+			e => "Couldn't find content referred to by maps: {e}"
+		);
+	}
+
+	if check_dependencies {
+		let has_problems = err_or_return!(
+			check_dependencies_of(&tree);
+			e => "Couldn't check dependencies: {e}"
+		);
+		if has_problems && strict {
+			return ExitCode::FAILURE
+		}
+	}
+
+	let mut to_compress = Vec::new();
+	struct ToCompress {
+		pub source: Source,
+		pub destination_path: PathBuf,
+	}
+
+	for Candidate { relative_path, state_path, fingerprint, source, .. } in tree.candidates {
+		let mut destination_path = dest_dir.join(&relative_path);
+		if let Some(extension) = destination_path.extension() {
+			let mut extension = extension.to_os_string();
+			extension.push(".bz2");
+			destination_path.set_extension(extension);
+		} else {
+			destination_path.set_extension("bz2");
+		}
+
+		if let Some(parent_path) = destination_path.parent() {
+			err_or_return!(
+				create_dir_all(parent_path);
+				e => "Couldn't create parent directories for {destination_path:?}: {e}"
+			);
+		}
+
+		if
+			!destination_path.exists()
+			|| state.time_of(&state_path) != Some(fingerprint)
+		{
+			err_or_return!(
+				state.set_time_of(&state_path, fingerprint);
+				e => "Couldn't write time for {source}: {e}"
+			);
+
+			to_compress.push(ToCompress {
+				source,
+				destination_path,
+			});
 		}
 	}
 
@@ -230,20 +412,17 @@ fn main() -> ExitCode {
 
 	rt.block_on(async move {
 		let mut task_set = JoinSet::new();
-		for ToCompress { source_path, destination_path } in to_compress {
+		for ToCompress { source, destination_path } in to_compress {
 			task_set.spawn_blocking(move || {
 				let destination = File::options()
 					.create(true).truncate(true).write(true)
 					.open(&destination_path)?;
 				let mut destination = BzEncoder::new(destination, compression);
-				let mut source = File::options().read(true).open(&source_path)?;
-				let mut buffer = [0u8; 1024];
-				while let Ok(n) = source.read(&mut buffer) {
-					if n == 0 { break }
-					destination.write_all(&buffer[..n])?;
-				}
+				let mut source_reader = source.open()?;
+				io::copy(&mut source_reader, &mut destination)
+					.map_err(|e| IoError::new(e.kind(), format!("Couldn't compress {source}: {e}")))?;
 				destination.finish()?;
-				Ok::<_, IoError>((source_path, destination_path))
+				Ok::<_, IoError>((source, destination_path))
 			});
 		}
 
@@ -251,7 +430,7 @@ fn main() -> ExitCode {
 		while let Some(join_result) = task_set.join_next().await {
 			match join_result {
 				Ok(Ok((source, destination))) => {
-					println!("{} => {}", source.display(), destination.display());
+					println!("{source} => {}", destination.display());
 				}
 				Ok(Err(e)) => {
 					failed = true;
@@ -268,38 +447,569 @@ fn main() -> ExitCode {
 	})
 }
 
+/// Files found in the source directory.
+#[derive(Default)]
+struct Tree {
+	/// Files that aren't ignored.
+	candidates: Vec<Candidate>,
+	/// Normalized paths of the files and directories that are ignored.
+	ignored: FxHashSet<String>,
+}
+
+impl Tree {
+	/// Returns `true` if the normalized `path`, or a directory containing it, is ignored.
+	fn is_ignored(&self, path: &str) -> bool {
+		self.ignored.contains(path)
+			|| path.match_indices('/').any(|(end, _)| self.ignored.contains(&path[..end]))
+	}
+}
+
+/// File that may need to be compressed.
+struct Candidate {
+	/// Path of the file as it will be served, relative to the destination directory.
+	relative_path: PathBuf,
+	/// Normalized form of `relative_path`, for comparing with paths that refer to content.
+	content_path: String,
+	/// Path under which the fingerprint of the file is kept in the state file.
+	state_path: PathBuf,
+	/// Value that changes when the contents of the file change.
+	fingerprint: u128,
+	source: Source,
+}
+
+/// Where the uncompressed data of a file is read from.
+enum Source {
+	File(PathBuf),
+	Vpk {
+		archive_path: PathBuf,
+		entry_path: String,
+		data_path: PathBuf,
+		offset: u64,
+		length: u32,
+		preload: Vec<u8>,
+	},
+}
+
+impl Source {
+	fn open(&self) -> IoResult<Box<dyn Read + Send>> {
+		match self {
+			Self::File(path) => Ok(Box::new(File::options().read(true).open(path)?)),
+			Self::Vpk { data_path, offset, length, preload, .. } => {
+				Ok(Box::new(vpk::open_data(data_path, *offset, *length, preload.clone())?))
+			}
+		}
+	}
+}
+
+impl Source {
+	/// Compute the size and CRC-32 checksum of the data.
+	fn checksum(&self) -> IoResult<(u64, u32)> {
+		let mut r = self.open()?;
+		let mut crc = Crc32::new();
+		let mut size = 0;
+		let mut buffer = [0u8; 1024];
+		loop {
+			let n = r.read(&mut buffer)?;
+			if n == 0 { break }
+			crc.update(&buffer[..n]);
+			size += n as u64;
+		}
+		Ok((size, crc.finish()))
+	}
+}
+
+impl fmt::Display for Source {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::File(path) => path.display().fmt(f),
+			Self::Vpk { archive_path, entry_path, .. } => write!(f, "{}:{entry_path}", archive_path.display()),
+		}
+	}
+}
+
+/// Add the files in the source directory at `source_dir` to `tree`, printing those that are ignored.
+///
+/// `root_scope` holds the ignore rules that apply to the whole directory, which `.fastdlignore` files add to, and
+/// VPKs are read if `expand_vpks` is `true`.
+fn push_dir_candidates(
+	source_dir: &Path, root_scope: Rc<IgnoreScope>, expand_vpks: bool, tree: &mut Tree,
+) -> Result<(), String> {
+	// Ignored directories are only traversed if paths in them may be excluded from being ignored.
+	let mut to_traverse = vec![(source_dir.to_path_buf(), root_scope, false)];
+	while let Some((dir, mut scope, is_dir_ignored)) = to_traverse.pop() {
+		let ignore_file_path = dir.join(IGNORE_FILE_NAME);
+		let mut patterns = PatternMap::new();
+		// Reading from the path makes `include` paths relative to the file, and lets includes of it be detected.
+		match patterns.read_file(&ignore_file_path) {
+			Ok(()) => scope = Rc::new(IgnoreScope { dir: dir.clone(), patterns, parent: Some(scope) }),
+			Err(e) if e.kind() == IoErrorKind::NotFound => {}
+			Err(e) => return Err(format!("Failed to read ignore file {ignore_file_path:?}: {e}")),
+		}
+
+		let items = dir.read_dir().map_err(|e| format!("Couldn't read directory {dir:?}: {e}"))?;
+
+		for item in items.flatten() {
+			let source_path = item.path();
+			let relative_path = source_path.strip_prefix(source_dir)
+				.expect("`item.path()` returns with prefix of `dir`");
+
+			let metadata = item.metadata()
+				.map_err(|e| format!("Couldn't get metadata for {source_path:?}: {e}"))?;
+
+			if metadata.is_file() && item.file_name() == IGNORE_FILE_NAME {
+				continue
+			}
+			if scope.is_ignored(&source_path, metadata.is_dir(), is_dir_ignored) {
+				if metadata.is_dir() && scope.may_exclude_in(&source_path) {
+					to_traverse.push((source_path, scope.clone(), true));
+					continue
+				}
+				println!("!{}", source_path.display());
+				tree.ignored.insert(content::normalize_path(&relative_path.to_string_lossy()));
+				continue
+			}
+
+			if metadata.is_dir() {
+				to_traverse.push((source_path, scope.clone(), false));
+			} else if expand_vpks && relative_path.extension().is_some_and(move |ext| ext.eq_ignore_ascii_case("vpk")) {
+				if vpk::is_directory_file(relative_path) {
+					push_vpk_candidates(&source_path, relative_path, &scope, tree)
+						.map_err(|e| format!("Couldn't read VPK {source_path:?}: {e}"))?;
+				}
+			} else {
+				let fs_time = metadata.modified()
+					.expect("last modification time should be supported")
+					.duration_since(SystemTime::UNIX_EPOCH)
+					.expect("system clock should be past the Unix epoch")
+					.as_secs();
+
+				tree.candidates.push(Candidate {
+					relative_path: relative_path.to_path_buf(),
+					content_path: content::normalize_path(&relative_path.to_string_lossy()),
+					state_path: relative_path.to_path_buf(),
+					fingerprint: fs_time.into(),
+					source: Source::File(source_path),
+				});
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Add the entries of the VPK at `vpk_path` to `tree`.
+///
+/// `relative_vpk_path` is the path of the VPK relative to the source directory, and `scope` holds the ignore rules
+/// that apply where the VPK is found.
+fn push_vpk_candidates(
+	vpk_path: &Path, relative_vpk_path: &Path,
+	scope: &IgnoreScope, tree: &mut Tree,
+) -> IoResult<()> {
+	let vpk = Vpk::open(vpk_path)?;
+	for entry in vpk.entries.iter() {
+		// As in directories, paths in ignored directories are ignored unless a rule matches them.
+		let is_in_ignored_dir = entry.path.match_indices('/').fold(false, move |is_ignored, (end, _)| {
+			scope.is_vpk_entry_ignored(&entry.path[..end], true, is_ignored)
+		});
+		let is_ignored = scope.is_vpk_entry_ignored(&entry.path, false, is_in_ignored_dir);
+		if is_ignored {
+			println!("!{}:{}", vpk_path.display(), entry.path);
+			tree.ignored.insert(content::normalize_path(&entry.path));
+			continue
+		}
+
+		let (data_path, offset) = vpk.data_location(entry)?;
+		tree.candidates.push(Candidate {
+			relative_path: PathBuf::from(&entry.path),
+			content_path: content::normalize_path(&entry.path),
+			state_path: relative_vpk_path.join(&entry.path),
+			fingerprint: entry.fingerprint(),
+			source: Source::Vpk {
+				archive_path: vpk_path.to_path_buf(),
+				entry_path: entry.path.clone(),
+				data_path,
+				offset,
+				length: entry.length,
+				preload: entry.preload.clone(),
+			},
+		});
+	}
+	Ok(())
+}
+
+/// Keep only the maps in `candidates` and the content that they refer to, directly or indirectly.
+///
+/// Candidates that are removed are printed.
+fn retain_referenced(candidates: &mut Vec<Candidate>) -> IoResult<()> {
+	let index = index_of(candidates);
+
+	let mut referenced = vec![false; candidates.len()];
+	let mut to_visit: Vec<usize> = candidates.iter()
+		.enumerate()
+		.filter(move |(_, candidate)| content::is_map(&candidate.content_path))
+		.map(move |(i, _)| i)
+		.collect();
+	while let Some(i) = to_visit.pop() {
+		let Candidate { content_path, source, .. } = &candidates[i];
+		if replace(&mut referenced[i], true) || !content::has_references(content_path) {
+			continue
+		}
+
+		let references = match source.open().and_then(|r| content::references(content_path, r)) {
+			Ok(references) => references,
+			// Maps with compressed lumps are still compressed, but the content that they refer to can't be found.
+			Err(e) if e.kind() == IoErrorKind::Unsupported => {
+				eprintln!("{source}: {e}, so content that it refers to isn't found");
+				continue
+			}
+			Err(e) => return Err(IoError::new(e.kind(), format!("{source}: {e}"))),
+		};
+		to_visit.extend(references.iter().filter_map(|path| index.get(path.as_str()).copied()));
+	}
+
+	let mut referenced = referenced.into_iter();
+	candidates.retain(move |candidate| {
+		let is_referenced = referenced.next().unwrap_or(false);
+		if !is_referenced {
+			println!("?{}", candidate.source);
+		}
+		is_referenced
+	});
+	Ok(())
+}
+
+/// Print the files packed into each map among `candidates`, along with the loose candidates that have the same
+/// path.
+///
+/// Returns `true` if any loose candidate differs from the packed file.
+fn check_pakfiles_of(candidates: &[Candidate]) -> IoResult<bool> {
+	let index = index_of(candidates);
+
+	let mut has_conflicts = false;
+	for candidate in candidates {
+		if !candidate.content_path.ends_with(".bsp") { continue }
+
+		let source = &candidate.source;
+		let entries = source.open()
+			.and_then(move |r| Bsp::read(r, &[bsp::LUMP_PAKFILE]))
+			.and_then(move |bsp| match bsp.lump(bsp::LUMP_PAKFILE) {
+				[] => Ok(Vec::new()),
+				pakfile => zip::entries(pakfile),
+			})
+			.map_err(move |e| IoError::new(e.kind(), format!("{source}: {e}")))?;
+
+		println!("{source}:");
+		for entry in entries {
+			let Some(&loose_index) = index.get(content::normalize_path(&entry.name).as_str()) else {
+				println!("\t{}", entry.name);
+				continue
+			};
+
+			let loose = &candidates[loose_index].source;
+			let (size, crc) = loose.checksum()
+				.map_err(move |e| IoError::new(e.kind(), format!("{loose}: {e}")))?;
+			if size == u64::from(entry.size) && crc == entry.crc {
+				println!("\t{} = {loose}", entry.name);
+			} else {
+				has_conflicts = true;
+				println!("\t{} != {loose}", entry.name);
+			}
+		}
+	}
+	Ok(has_conflicts)
+}
+
+/// Print the dependencies and companion files of the candidates in `tree` that are missing or ignored.
+///
+/// Returns `true` if any were found.
+fn check_dependencies_of(tree: &Tree) -> IoResult<bool> {
+	let index = index_of(&tree.candidates);
+
+	let mut has_problems = false;
+	for Candidate { content_path, source, .. } in tree.candidates.iter() {
+		for (companion, is_required) in content::companions(content_path) {
+			if index.contains_key(companion.as_str()) { continue }
+			if tree.is_ignored(&companion) {
+				has_problems = true;
+				eprintln!("{source}: depends on ignored {companion}");
+			} else if is_required {
+				has_problems = true;
+				eprintln!("{source}: depends on missing {companion}");
+			}
+		}
+
+		if !content::has_dependencies(content_path) { continue }
+
+		let dependencies = source.open()
+			.and_then(|r| content::dependencies(content_path, r))
+			.map_err(move |e| IoError::new(e.kind(), format!("{source}: {e}")))?;
+		for dependency in dependencies {
+			if index.contains_key(dependency.as_str()) { continue }
+			has_problems = true;
+			if tree.is_ignored(&dependency) {
+				eprintln!("{source}: depends on ignored {dependency}");
+			} else {
+				eprintln!("{source}: depends on missing {dependency}");
+			}
+		}
+	}
+	Ok(has_problems)
+}
+
+/// Rewrite the paths that `candidates` are served from with `rules`.
+fn rewrite_candidates(candidates: &mut [Candidate], rules: &RewriteRules) {
+	for candidate in candidates.iter_mut() {
+		let path = candidate.relative_path.to_string_lossy();
+		if let Cow::Owned(rewritten) = rules.rewrite(&path) {
+			candidate.content_path = content::normalize_path(&rewritten);
+			candidate.relative_path = PathBuf::from(rewritten);
+		}
+	}
+}
+
+/// Print the candidates that are served from the same path, returning `true` if there are any.
+fn report_collisions(candidates: &[Candidate]) -> bool {
+	let mut sources = FxHashMap::<&Path, &Source>::default();
+	let mut has_collisions = false;
+	for Candidate { relative_path, source, .. } in candidates.iter() {
+		if let Some(other) = sources.insert(relative_path, source) {
+			eprintln!("{other} and {source} are both served from {}", relative_path.display());
+			has_collisions = true;
+		}
+	}
+	has_collisions
+}
+
+/// Map the normalized paths of `candidates` to their indices.
+fn index_of(candidates: &[Candidate]) -> FxHashMap<&str, usize> {
+	candidates.iter()
+		.enumerate()
+		.map(move |(i, candidate)| (candidate.content_path.as_str(), i))
+		.collect()
+}
+
+/// Name of the ignore files that are read from the source directory and its subdirectories, with rules for the paths
+/// in the directory that they're in.
+const IGNORE_FILE_NAME: &str = ".fastdlignore";
+
+/// Ignore rules that apply within a directory, layered over those of the directories containing it, like nested
+/// `.gitignore` files.
+#[derive(Debug)]
+struct IgnoreScope {
+	/// Directory that the paths matched by `patterns` are relative to.
+	dir: PathBuf,
+	patterns: PatternMap,
+	parent: Option<Rc<IgnoreScope>>,
+}
+
+impl IgnoreScope {
+	/// Returns `true` if the last rule that matches `path`, in the innermost scope that has one, ignores it, or if no
+	/// rule matches it and it's in an ignored directory.
+	fn is_ignored(&self, path: &Path, is_dir: bool, is_in_ignored_dir: bool) -> bool {
+		self.innermost_directive(move |dir| path.strip_prefix(dir).ok(), is_dir)
+			.map_or(is_in_ignored_dir, move |directive| directive == Directive::Include)
+	}
+
+	/// Like [`Self::is_ignored`], but for the path of an entry in a VPK found in this scope.
+	///
+	/// Entries are served as if the VPK were extracted to the source directory, so every scope matches their path
+	/// as is, rather than relative to the scope's directory.
+	fn is_vpk_entry_ignored(&self, path: &str, is_dir: bool, is_in_ignored_dir: bool) -> bool {
+		self.innermost_directive(move |_| Some(Path::new(path)), is_dir)
+			.map_or(is_in_ignored_dir, move |directive| directive == Directive::Include)
+	}
+
+	/// Get the directive of the last rule that matches a path, in the innermost scope that has one.
+	///
+	/// `relative_path` gets the path relative to the directory of a scope, or [`None`] if the scope doesn't apply.
+	fn innermost_directive<'a>(
+		&self, relative_path: impl Fn(&Path) -> Option<&'a Path>, is_dir: bool,
+	) -> Option<Directive> {
+		let mut scope = Some(self);
+		while let Some(Self { dir, patterns, parent }) = scope {
+			if let Some(relative_path) = relative_path(dir) {
+				let relative_path = relative_path.as_os_str().as_encoded_bytes();
+				let directive = if is_dir {
+					patterns.dir_directive_of(relative_path)
+				} else {
+					patterns.directive_of(relative_path)
+				};
+				if directive.is_some() {
+					return directive
+				}
+			}
+			scope = parent.as_deref();
+		}
+		None
+	}
+
+	/// Returns `true` if a rule in any scope may exclude a path in the directory at `dir` from being ignored.
+	fn may_exclude_in(&self, dir: &Path) -> bool {
+		let mut scope = Some(self);
+		while let Some(Self { dir: scope_dir, patterns, parent }) = scope {
+			if let Ok(relative_dir) = dir.strip_prefix(scope_dir) {
+				if patterns.may_exclude_in_dir(relative_dir.as_os_str().as_encoded_bytes()) {
+					return true
+				}
+			}
+			scope = parent.as_deref();
+		}
+		false
+	}
+}
+
+/// Ignore patterns and expressions, in the order of the lines that they're read from, so that later lines override
+/// earlier ones.
+///
+/// The patterns are compiled so that each path is matched against all of them at once, while expressions are
+/// matched one by one. Patterns that follow a `case sensitive` line are compiled separately, to be matched with
+/// [`ExactPathMatch`].
 #[derive(Default, Debug, Clone)]
-#[repr(transparent)]
-pub struct PatternMap(pub FxHashMap<Pattern, Directive>);
+pub struct PatternMap {
+	/// Patterns with the index of the line that they're read from, among all rules.
+	patterns: PatternSet<U8Pieces, u8, (usize, Directive), CaseInsensitivePathMatch>,
+	/// Case-sensitive patterns with the index of the line that they're read from, among all rules.
+	case_sensitive_patterns: PatternSet<U8Pieces, u8, (usize, Directive), ExactPathMatch>,
+	expressions: Vec<ExpressionRule>,
+}
+
+/// Grouped expression read from an ignore file.
+#[derive(Debug, Clone)]
+struct ExpressionRule {
+	/// Index of the line that the expression is read from, among all rules.
+	order: usize,
+	expr: Expr,
+	/// Whether the expression has a `!`, so that it isn't matched against directories.
+	has_negation: bool,
+	directive: Directive,
+	case: Case,
+}
 
 impl PatternMap {
 	#[inline]
 	pub fn new() -> Self {
-		Self(FxHashMap::with_hasher(FxBuildHasher))
+		Self::default()
 	}
 
+	/// Returns `true` if a pattern matches `haystack`, once they're both normalized.
 	pub fn has_match(&self, haystack: &[u8]) -> bool {
-		let mut one_matched = false;
-		for (pattern, directive) in self.0.iter() {
-			if pattern.first_match(PathMatch, haystack).is_some() {
-				match directive {
-					Directive::Include => {
-						one_matched = true;
-					}
-					Directive::Exclude => {
-						return false
-					}
-				}
-			}
-		}
-		one_matched
+		self.directive_of(haystack) == Some(Directive::Include)
 	}
 
-	pub fn insert(&mut self, pattern: Pattern, directive: Directive) {
-		self.0.insert(pattern, directive);
+	/// Returns `true` if a pattern matches the directory at `path`, with or without a trailing separator.
+	pub fn has_dir_match(&self, path: &[u8]) -> bool {
+		self.dir_directive_of(path) == Some(Directive::Include)
 	}
 
-	pub fn read_from<R: BufRead>(&mut self, mut r: R) -> IoResult<()> {
+	/// Get the directive of the last rule that matches `haystack`, like [`has_match`](Self::has_match).
+	pub fn directive_of(&self, haystack: &[u8]) -> Option<Directive> {
+		let haystack = normalize(haystack);
+		self.last_match(&[&haystack], false)
+	}
+
+	/// Get the directive of the last rule that matches the directory at `path`, like
+	/// [`has_dir_match`](Self::has_dir_match).
+	///
+	/// Expressions with a `!` are skipped, as they may not match the paths in a directory that they match, so they're
+	/// only matched against those paths.
+	pub fn dir_directive_of(&self, path: &[u8]) -> Option<Directive> {
+		let path = normalize(path);
+		let path = path.as_ref();
+		let mut path_with_separator = Vec::with_capacity(path.len() + 1);
+		path_with_separator.extend_from_slice(path);
+		path_with_separator.push(b'/');
+		self.last_match(&[path, &path_with_separator], true)
+	}
+
+	/// Returns `true` if a `!` rule may match a path in the directory at `path`, which may then be excluded from
+	/// being ignored even if the directory is.
+	pub fn may_exclude_in_dir(&self, path: &[u8]) -> bool {
+		let path = normalize(path);
+		let mut prefix = Vec::with_capacity(path.len() + 1);
+		prefix.extend_from_slice(&path);
+		prefix.push(b'/');
+		// Expressions can't be matched against prefixes, so any of them may match.
+		may_exclude_with_prefix(self.patterns.rules(), CaseInsensitivePathMatch, &prefix)
+			|| may_exclude_with_prefix(self.case_sensitive_patterns.rules(), ExactPathMatch, &prefix)
+			|| self.expressions.iter().any(move |rule| rule.directive == Directive::Exclude)
+	}
+
+	/// Get the directive of the last rule that matches any of `haystacks`, skipping expressions with a `!` if they're
+	/// the paths of a directory.
+	fn last_match(&self, haystacks: &[&[u8]], is_dir: bool) -> Option<Directive> {
+		let last_pattern = last_pattern_match(&self.patterns, haystacks)
+			.max(last_pattern_match(&self.case_sensitive_patterns, haystacks));
+		let last_expression = self.expressions.iter()
+			.rev()
+			.filter(move |rule| !(is_dir && rule.has_negation))
+			.find(move |rule| haystacks.iter().any(move |haystack| rule.case.is_match(&rule.expr, haystack)))
+			.map(move |rule| (rule.order, rule.directive));
+		last_pattern.max(last_expression).map(move |(_, directive)| directive)
+	}
+
+	/// Read rules from `r`, which start out ignoring letter case, with `include` paths relative to the current
+	/// directory.
+	pub fn read_from<R: BufRead>(&mut self, r: R) -> IoResult<()> {
+		self.push_rules_with(move |rules| rules.read_from(r, Path::new(""), &mut Vec::new()))
+	}
+
+	/// Read rules from the file at `path`, like [`read_from`](Self::read_from), with `include` paths relative to the
+	/// directory of the file.
+	pub fn read_file(&mut self, path: &Path) -> IoResult<()> {
+		self.push_rules_with(move |rules| rules.read_file(path, &mut Vec::new()))
+	}
+
+	/// Push a rule for each pattern in `patterns`, written as in an ignore file but without a leading `!`, ignoring
+	/// letter case.
+	pub fn push_patterns<'a>(&mut self, patterns: impl IntoIterator<Item = (&'a str, Directive)>) -> Result<(), String> {
+		self.push_rules_with(move |rules| patterns.into_iter().try_for_each(move |(pattern, directive)| {
+			rules.push_rule(pattern, directive, Case::Insensitive).map_err(move |e| format!("{pattern:?}: {e}"))
+		}))
+	}
+
+	/// Push rules with `push`, after the rules that are already in the map, and compile them.
+	fn push_rules_with<E>(&mut self, push: impl FnOnce(&mut Rules) -> Result<(), E>) -> Result<(), E> {
+		let mut rules = Rules {
+			rules: take(&mut self.patterns).into_rules(),
+			case_sensitive_rules: take(&mut self.case_sensitive_patterns).into_rules(),
+			expressions: take(&mut self.expressions),
+		};
+		let result = push(&mut rules);
+		self.patterns = PatternSet::new(rules.rules, CaseInsensitivePathMatch);
+		self.case_sensitive_patterns = PatternSet::new(rules.case_sensitive_rules, ExactPathMatch);
+		self.expressions = rules.expressions;
+		result
+	}
+}
+
+/// Rules of a [`PatternMap`] while they're being read, before its patterns are compiled.
+struct Rules {
+	rules: RuleSet<U8Pieces, u8, (usize, Directive)>,
+	case_sensitive_rules: RuleSet<U8Pieces, u8, (usize, Directive)>,
+	expressions: Vec<ExpressionRule>,
+}
+
+/// Line of an ignore file that isn't a rule.
+enum NonRule<'a> {
+	Case(Case),
+	Include(&'a str),
+}
+
+impl Rules {
+	/// Read rules from the file at `path`, unless it's in `files`, which are the files that are being read.
+	fn read_file(&mut self, path: &Path, files: &mut Vec<PathBuf>) -> IoResult<()> {
+		let file = File::open(path)?;
+		let canonical_path = path.canonicalize()?;
+		if files.contains(&canonical_path) {
+			return Err(IoError::new(IoErrorKind::InvalidData, format!("{path:?} includes itself")))
+		}
+		files.push(canonical_path);
+		let result = self.read_from(BufReader::new(file), path.parent().unwrap_or(Path::new("")), files);
+		files.pop();
+		result
+	}
+
+	/// Read rules from `r`, with `include` paths relative to `dir`.
+	fn read_from<R: BufRead>(&mut self, mut r: R, dir: &Path, files: &mut Vec<PathBuf>) -> IoResult<()> {
 		struct ClearGuard<'a>(&'a mut String);
 		impl Deref for ClearGuard<'_> {
 			type Target = String;
@@ -319,31 +1029,361 @@ impl PatternMap {
 		}
 
 		let mut line = String::new();
+		let mut line_number = 0;
+		let mut case = Case::Insensitive;
 		while r.read_line(&mut line)? != 0 {
 			let line = ClearGuard(&mut line);
-			let trimmed_line = line.trim();
-			let Some((first, rest)) = trimmed_line.split_at_checked(1) else {
-				// We skip a `trimmed_line.is_empty()` check this way, too.
-				continue
-			};
-			
-			let (pattern_str, directive) = match first {
-				"#" => continue,
-				"!" => (rest, Directive::Exclude),
-				"\\" => (rest, Directive::Include),
-				_ => (trimmed_line, Directive::Include),
-			};
+			line_number += 1;
+			let line_error = move |e| IoError::new(IoErrorKind::InvalidData, format!("line {line_number}: {e}"));
+			match self.push_line(&line, case).map_err(line_error)? {
+				Some(NonRule::Case(line_case)) => case = line_case,
+				Some(NonRule::Include(path)) => {
+					let path = dir.join(path);
+					self.read_file(&path, files).map_err(move |e| line_error(format!("couldn't include {path:?}: {e}")))?;
+				}
+				None => {}
+			}
+		}
+
+		Ok(())
+	}
 
-			let pattern = Pattern::parse(pattern_str.as_bytes(), &b'*');
-			self.insert(pattern, directive);
+	/// Push the rule that `line` of an ignore file is written as, with `case`, unless it's another kind of line.
+	fn push_line<'l>(&mut self, line: &'l str, case: Case) -> Result<Option<NonRule<'l>>, String> {
+		let trimmed_line = trim_pattern_line(line);
+		let Some((first, rest)) = trimmed_line.split_at_checked(1) else {
+			// We skip a `trimmed_line.is_empty()` check this way, too.
+			return Ok(None)
+		};
+		match trimmed_line {
+			"case sensitive" => return Ok(Some(NonRule::Case(Case::Sensitive))),
+			"case insensitive" => return Ok(Some(NonRule::Case(Case::Insensitive))),
+			_ => {}
+		}
+		if let Some(path) = trimmed_line.strip_prefix("include ") {
+			return Ok(Some(NonRule::Include(path.trim_start())))
 		}
 
+		// A leading `\` is left for the pattern to escape what follows it.
+		let (pattern_str, directive) = match first {
+			"#" => return Ok(None),
+			"!" => (rest, Directive::Exclude),
+			_ => (trimmed_line, Directive::Include),
+		};
+
+		self.push_rule(pattern_str, directive, case)?;
+		Ok(None)
+	}
+
+	/// Push a rule with `pattern_str`, which is a pattern, or an expression if it starts with `expr `.
+	fn push_rule(&mut self, pattern_str: &str, directive: Directive, case: Case) -> Result<(), String> {
+		let order = self.rules.len() + self.case_sensitive_rules.len() + self.expressions.len();
+		if let Some(expression) = pattern_str.strip_prefix("expr ") {
+			let expr = parse_expression(expression, parse_ignore_pattern)?;
+			let has_negation = has_negation(&expr);
+			self.expressions.push(ExpressionRule { order, expr, has_negation, directive, case });
+		} else {
+			let rules = match case {
+				Case::Insensitive => &mut self.rules,
+				Case::Sensitive => &mut self.case_sensitive_rules,
+			};
+			rules.push(parse_ignore_pattern(pattern_str), (order, directive));
+		}
 		Ok(())
 	}
 }
 
+/// Get the index of the line of the last pattern in `patterns` that matches any of `haystacks`, with its directive.
+fn last_pattern_match<M: KeyMatcher<u8>>(
+	patterns: &PatternSet<U8Pieces, u8, (usize, Directive), M>, haystacks: &[&[u8]],
+) -> Option<(usize, Directive)> {
+	let rules = patterns.rules().rules();
+	haystacks.iter()
+		.filter_map(move |haystack| patterns.last_match_index(haystack))
+		.max()
+		.map(move |index| rules[index].1)
+}
+
+/// Returns `true` if a `!` rule in `rules` matches some path that starts with `prefix`.
+fn may_exclude_with_prefix<M: Matcher<u8>>(
+	rules: &RuleSet<U8Pieces, u8, (usize, Directive)>, matcher: M, prefix: &[u8],
+) -> bool {
+	rules.rules().iter()
+		.any(move |(pattern, (_, directive))| *directive == Directive::Exclude && pattern.is_prefix_match(&matcher, prefix))
+}
+
+/// Parse a pattern from an ignore file, matching whole paths from the source directory, normalized.
+fn parse_ignore_pattern(pattern: &str) -> Pattern {
+	let pattern = full_path_pattern(pattern);
+	let pattern = normalize(pattern.as_bytes());
+	Pattern::parse_with(&pattern, &Syntax::<u8>::PATH_GLOB)
+}
+
+/// Trim whitespace from both ends of `line`, except for a whitespace character escaped by a `\` at the end.
+fn trim_pattern_line(line: &str) -> &str {
+	let line = line.trim_start().trim_end_matches(['\r', '\n']);
+	let trimmed = line.trim_end();
+	let escapes = trimmed.bytes().rev().take_while(move |b| *b == b'\\').count();
+	if escapes % 2 == 0 || trimmed.len() == line.len() {
+		return trimmed
+	}
+	let escaped_len = line[trimmed.len()..].chars().next().map_or(0, char::len_utf8);
+	&line[..trimmed.len() + escaped_len]
+}
+
+/// Convert a pattern from an ignore file into one that matches whole paths from the source directory, as patterns
+/// in `.gitignore` files do.
+///
+/// Patterns without a `/` before their end match at any depth, and a leading `/` is only an anchor.
+fn full_path_pattern(pattern: &str) -> Cow<'_, str> {
+	if let Some(anchored) = pattern.strip_prefix('/') {
+		return Cow::Borrowed(anchored)
+	}
+	if pattern.strip_suffix('/').unwrap_or(pattern).contains('/') {
+		Cow::Borrowed(pattern)
+	} else {
+		Cow::Owned(format!("**/{pattern}"))
+	}
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Directive {
 	Include,
 	Exclude,
 }
+
+/// Whether rules in an ignore file match letter case, as set by its `case sensitive` and `case insensitive` lines.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+	#[default]
+	Insensitive,
+	Sensitive,
+}
+
+impl Case {
+	/// Returns `true` if `condition` matches `haystack`, with the matcher for this case.
+	pub fn is_match<C: Condition<u8>>(self, condition: &C, haystack: &[u8]) -> bool {
+		match self {
+			Self::Insensitive => condition.is_match(&CaseInsensitivePathMatch, haystack),
+			Self::Sensitive => condition.is_match(&ExactPathMatch, haystack),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn candidate(path: &str) -> Candidate {
+		Candidate {
+			relative_path: PathBuf::from(path),
+			content_path: content::normalize_path(path),
+			state_path: PathBuf::from(path),
+			fingerprint: 0,
+			source: Source::File(PathBuf::from("src").join(path)),
+		}
+	}
+
+	/// Directory under the temporary directory, which is removed when dropped.
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new(name: &str) -> Self {
+			let path = std::env::temp_dir().join(format!("bubz2-test-{}-{name}", std::process::id()));
+			let _ = std::fs::remove_dir_all(&path);
+			create_dir_all(&path).unwrap();
+			Self(path)
+		}
+
+		fn write(&self, path: &str, contents: &str) {
+			let path = self.0.join(path);
+			create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.0);
+		}
+	}
+
+	/// Walk a temporary directory holding `files`, given as paths and contents, with the rules in `ignore`, getting
+	/// the sorted paths of the candidates and of what's ignored.
+	fn walk(name: &str, files: &[(&str, &str)], ignore: &str) -> Result<(Vec<String>, Vec<String>), String> {
+		let dir = TempDir::new(name);
+		for (path, contents) in files {
+			dir.write(path, contents);
+		}
+		let mut tree = Tree::default();
+		let scope = Rc::new(IgnoreScope { dir: dir.0.clone(), patterns: read_patterns(ignore), parent: None });
+		push_dir_candidates(&dir.0, scope, false, &mut tree)?;
+		let mut candidates: Vec<_> = tree.candidates.into_iter().map(move |candidate| candidate.content_path).collect();
+		let mut ignored: Vec<_> = tree.ignored.into_iter().collect();
+		candidates.sort();
+		ignored.sort();
+		Ok((candidates, ignored))
+	}
+
+	const FILES: &[(&str, &str)] = &[
+		("materials/a.vmt", ""), ("materials/dev/b.vmt", ""), ("sound/c.wav", ""), ("maps/d.bsp", ""),
+	];
+
+	#[test]
+	fn negated_expressions_are_matched_per_file() {
+		let (candidates, ignored) = walk("negated-expressions", FILES, "expr materials/** & !materials/dev/**").unwrap();
+		assert_eq!(candidates, ["maps/d.bsp", "materials/dev/b.vmt", "sound/c.wav"]);
+		assert_eq!(ignored, ["materials/a.vmt"]);
+
+		// Expressions without a `!` match the paths in a directory that they match, so it's ignored as a whole.
+		let (candidates, ignored) = walk("expressions", FILES, "expr sound/** | maps/**").unwrap();
+		assert_eq!(candidates, ["materials/a.vmt", "materials/dev/b.vmt"]);
+		assert_eq!(ignored, ["maps", "sound"]);
+	}
+
+	#[test]
+	fn ignore_files_include_relative_to_themselves() {
+		let mut files = [
+			("materials/a.vmt", ""),
+			("materials/b.vtf", ""),
+			("sound/c.wav", ""),
+			("materials/.fastdlignore", "include rules/textures.txt"),
+			("materials/rules/textures.txt", "*.vtf"),
+		];
+		let (candidates, ignored) = walk("ignore-file-includes", &files, "rules/").unwrap();
+		assert_eq!(candidates, ["materials/a.vmt", "sound/c.wav"]);
+		assert_eq!(ignored, ["materials/b.vtf", "materials/rules"]);
+
+		files[4].1 = "*.vtf\ninclude ../.fastdlignore";
+		let e = walk("ignore-file-cycles", &files, "rules/").unwrap_err();
+		assert!(e.contains("includes itself"), "{e}");
+	}
+
+	fn read_patterns(text: &str) -> PatternMap {
+		let mut map = PatternMap::new();
+		map.read_from(text.as_bytes()).unwrap();
+		map
+	}
+
+	#[test]
+	fn parenthesized_lines_are_patterns() {
+		let map = read_patterns("(1).bsp\n(copy)*.vmt\n");
+		assert!(map.has_match(b"maps/(1).bsp"));
+		assert!(map.has_match(b"materials/(copy) wall.vmt"));
+		assert!(!map.has_match(b"maps/1.bsp"));
+	}
+
+	#[test]
+	fn expr_lines_are_expressions() {
+		let map = read_patterns("expr materials/** & (*.vtf | *.vmt) & !materials/dev/**\n!expr \\(1\\).vtf | *.txt");
+		assert!(map.has_match(b"materials/brick/wall.vtf"));
+		assert!(!map.has_match(b"materials/dev/wall.vtf"));
+		assert!(!map.has_match(b"materials/brick/wall.vtx"));
+		assert!(!map.has_match(b"materials/brick/(1).vtf"));
+
+		let mut map = PatternMap::new();
+		let e = map.read_from("a.txt\nexpr (a.txt".as_bytes()).unwrap_err();
+		assert_eq!(e.to_string(), "line 2: expected `)`, found end of line");
+	}
+
+	#[test]
+	fn case_directive_applies_until_changed() {
+		let map = read_patterns("*.bsp\ncase sensitive\n!Maps/keep.bsp\nSound/\ncase insensitive\nmodels/");
+		assert!(!map.has_match(b"Maps/keep.bsp"));
+		assert!(map.has_match(b"maps/keep.bsp"));
+		assert!(map.has_dir_match(b"Sound"));
+		assert!(!map.has_dir_match(b"sound"));
+		assert!(map.has_dir_match(b"MODELS"));
+	}
+
+	#[test]
+	fn inner_scopes_override_outer_ones() {
+		let root = Rc::new(IgnoreScope {
+			dir: PathBuf::from("src"),
+			patterns: read_patterns("*.txt\nsound/"),
+			parent: None,
+		});
+		let addon = IgnoreScope {
+			dir: PathBuf::from("src/addon"),
+			patterns: read_patterns("!*.txt\n/sound/"),
+			parent: Some(root.clone()),
+		};
+
+		assert!(addon.is_ignored(Path::new("src/readme.txt"), false, false));
+		assert!(!addon.is_ignored(Path::new("src/addon/readme.txt"), false, false));
+		// Rules of the addon's ignore file are relative to its directory.
+		assert!(addon.is_ignored(Path::new("src/addon/sound"), true, false));
+		assert!(!addon.is_ignored(Path::new("src/addon/materials"), true, false));
+		// Outer rules apply where inner ones don't match.
+		assert!(addon.is_ignored(Path::new("src/addon/materials/sound"), true, false));
+		assert!(root.is_ignored(Path::new("src/sound"), true, false));
+
+		let files = [
+			("addon/.fastdlignore", "!/a.txt\n/sound/"), ("addon/a.txt", ""), ("addon/sound/b.wav", ""), ("c.txt", ""),
+		];
+		let (candidates, ignored) = walk("ignore-files", &files, "*.txt").unwrap();
+		assert_eq!(candidates, ["addon/a.txt"]);
+		assert_eq!(ignored, ["addon/sound", "c.txt"]);
+	}
+
+	#[test]
+	fn reexcluded_files_in_ignored_dirs_are_kept() {
+		let files = [("sound/custom/intro.wav", ""), ("sound/custom/outro.wav", ""), ("sound/ambient/wind.wav", "")];
+		let (candidates, ignored) = walk("reexcluded-files", &files, "sound/\n!sound/custom/intro.wav").unwrap();
+		assert_eq!(candidates, ["sound/custom/intro.wav"]);
+		assert_eq!(ignored, ["sound/ambient", "sound/custom/outro.wav"]);
+
+		let ignore = "materials/\n!expr materials/** & !materials/dev/**";
+		let (candidates, ignored) = walk("reexcluded-expressions", FILES, ignore).unwrap();
+		assert_eq!(candidates, ["maps/d.bsp", "materials/a.vmt", "sound/c.wav"]);
+		assert_eq!(ignored, ["materials/dev/b.vmt"]);
+
+		let scope = IgnoreScope {
+			dir: PathBuf::from("src"),
+			patterns: read_patterns("sound/\n!sound/custom/intro.wav"),
+			parent: None,
+		};
+		assert!(!scope.is_ignored(Path::new("src/sound/custom/intro.wav"), false, true));
+		assert!(scope.is_ignored(Path::new("src/sound/custom/outro.wav"), false, true));
+		assert!(scope.may_exclude_in(Path::new("src/sound")));
+		assert!(scope.may_exclude_in(Path::new("src/sound/custom")));
+		assert!(!scope.may_exclude_in(Path::new("src/sound/ambient")));
+
+		let map = read_patterns("*.bsp\ncase sensitive\n!Maps/keep.bsp");
+		assert!(map.may_exclude_in_dir(b"Maps"));
+		assert!(!map.may_exclude_in_dir(b"maps"));
+	}
+
+	#[test]
+	fn maps_with_compressed_lumps_are_kept() {
+		// A header with only the entity lump, right after it, compressed.
+		let mut bsp = b"VBSP\x14\0\0\0".to_vec();
+		bsp.extend_from_slice(&[(8 + 64 * 16 + 4u32).to_le_bytes(), 4u32.to_le_bytes()].concat());
+		bsp.resize(8 + 64 * 16 + 4, 0);
+		bsp.extend_from_slice(b"LZMA");
+
+		let dir = TempDir::new("compressed-maps");
+		dir.write("maps/a.bsp", std::str::from_utf8(&bsp).unwrap());
+		dir.write("materials/b.vmt", "");
+		let mut candidates = Vec::from(["maps/a.bsp", "materials/b.vmt"].map(|path| Candidate {
+			source: Source::File(dir.0.join(path)),
+			..candidate(path)
+		}));
+		retain_referenced(&mut candidates).unwrap();
+		assert_eq!(candidates.len(), 1);
+		assert_eq!(candidates[0].content_path, "maps/a.bsp");
+	}
+
+	#[test]
+	fn reports_rewritten_collisions() {
+		let rules = RewriteRules::read_from("lowercase\ncustom/*/** -> $2".as_bytes()).unwrap();
+		let mut candidates = vec![candidate("custom/a/materials/x.vmt"), candidate("custom/b/Materials/Y.vmt")];
+		rewrite_candidates(&mut candidates, &rules);
+		assert_eq!(candidates[1].relative_path, Path::new("materials/y.vmt"));
+		assert!(!report_collisions(&candidates));
+
+		candidates.push(candidate("custom/c/MATERIALS/X.VMT"));
+		rewrite_candidates(&mut candidates[2..], &rules);
+		assert!(report_collisions(&candidates));
+	}
+}
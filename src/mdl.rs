@@ -0,0 +1,142 @@
+use std::io::{
+	Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
+};
+
+use crate::bytes::{
+	le_u32, cstr_at,
+};
+
+const IDENT: &[u8; 4] = b"IDST";
+const NUM_TEXTURES_OFFSET: usize = 204;
+const TEXTURE_SIZE: usize = 64;
+
+/// Extensions of the files that accompany a `.mdl` file with the same base name.
+pub const COMPANION_EXTENSIONS: &[&str] = &[
+	"vvd", "dx90.vtx", "dx80.vtx", "sw.vtx", "vtx", "phy", "ani",
+];
+
+/// Extensions of the companion files without which a model can't be loaded.
+pub const REQUIRED_COMPANION_EXTENSIONS: &[&str] = &["vvd", "dx90.vtx"];
+
+/// Get the paths of the materials that the model in `data` may use, relative to the `materials` directory and
+/// without an extension.
+///
+/// Each texture is looked up in every texture directory, so not all of the returned paths are expected to exist.
+pub fn material_names(data: &[u8]) -> IoResult<Vec<String>> {
+	if !data.starts_with(IDENT) {
+		return Err(IoError::new(IoErrorKind::InvalidData, "not a model file"))
+	}
+	let header = move |index: usize| {
+		le_u32(data, NUM_TEXTURES_OFFSET + index * 4)
+			.map(move |n| n as usize)
+			.ok_or_else(move || IoError::new(IoErrorKind::UnexpectedEof, "model header is truncated"))
+	};
+	let (texture_count, texture_offset) = (header(0)?, header(1)?);
+	let (dir_count, dir_offset) = (header(2)?, header(3)?);
+	let texture_table = table(data, texture_offset, texture_count, TEXTURE_SIZE, "texture")?;
+	let dir_table = table(data, dir_offset, dir_count, 4, "texture directory")?;
+
+	let mut textures = Vec::with_capacity(texture_count);
+	for (index, texture) in texture_table.chunks_exact(TEXTURE_SIZE).enumerate() {
+		let name_offset = le_u32(texture, 0).expect("texture entries are longer than 4 bytes") as usize;
+		let name_offset = (texture_offset + index * TEXTURE_SIZE).checked_add(name_offset)
+			.ok_or_else(move || IoError::new(IoErrorKind::InvalidData, "texture name is out of bounds"))?;
+		textures.push(cstr_at(data, name_offset));
+	}
+
+	let name_count = texture_count.checked_mul(dir_count)
+		.ok_or_else(move || IoError::new(IoErrorKind::InvalidData, "model lists too many materials"))?;
+	let mut names = Vec::with_capacity(name_count);
+	for dir in dir_table.chunks_exact(4) {
+		let dir = cstr_at(data, le_u32(dir, 0).expect("directory entries are 4 bytes") as usize);
+		for texture in textures.iter() {
+			names.push(format!("{dir}{texture}"));
+		}
+	}
+	Ok(names)
+}
+
+/// Get the table of `count` entries of `entry_size` bytes at `offset` in `data`, failing if it's out of bounds.
+fn table<'a>(data: &'a [u8], offset: usize, count: usize, entry_size: usize, name: &str) -> IoResult<&'a [u8]> {
+	count.checked_mul(entry_size)
+		.and_then(move |size| data.get(offset..offset.checked_add(size)?))
+		.ok_or_else(move || IoError::new(IoErrorKind::InvalidData, format!("{name} table is out of bounds")))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HEADER_SIZE: usize = NUM_TEXTURES_OFFSET + 16;
+
+	/// Build a model with `textures` in `dirs`, with the texture table after the header, followed by the directory
+	/// table and then the strings.
+	fn build(textures: &[&str], dirs: &[&str]) -> Vec<u8> {
+		let texture_offset = HEADER_SIZE;
+		let dir_offset = texture_offset + textures.len() * TEXTURE_SIZE;
+		let mut strings_offset = dir_offset + dirs.len() * 4;
+
+		let mut data = vec![0; strings_offset];
+		data[..4].copy_from_slice(IDENT);
+		for (index, value) in [textures.len(), texture_offset, dirs.len(), dir_offset].into_iter().enumerate() {
+			let base = NUM_TEXTURES_OFFSET + index * 4;
+			data[base..base + 4].copy_from_slice(&(value as u32).to_le_bytes());
+		}
+		let mut strings = Vec::new();
+		for (index, texture) in textures.iter().enumerate() {
+			let base = texture_offset + index * TEXTURE_SIZE;
+			data[base..base + 4].copy_from_slice(&((strings_offset - base) as u32).to_le_bytes());
+			strings_offset += texture.len() + 1;
+			strings.extend_from_slice(texture.as_bytes());
+			strings.push(0);
+		}
+		for (index, dir) in dirs.iter().enumerate() {
+			let base = dir_offset + index * 4;
+			data[base..base + 4].copy_from_slice(&(strings_offset as u32).to_le_bytes());
+			strings_offset += dir.len() + 1;
+			strings.extend_from_slice(dir.as_bytes());
+			strings.push(0);
+		}
+		data.extend_from_slice(&strings);
+		data
+	}
+
+	fn set_header(data: &mut [u8], index: usize, value: u32) {
+		let base = NUM_TEXTURES_OFFSET + index * 4;
+		data[base..base + 4].copy_from_slice(&value.to_le_bytes());
+	}
+
+	#[test]
+	fn lists_textures_in_every_directory() {
+		let data = build(&["wall", "floor"], &["models/props/", "shared/"]);
+		assert_eq!(material_names(&data).unwrap(), [
+			"models/props/wall", "models/props/floor", "shared/wall", "shared/floor",
+		]);
+		assert!(material_names(&build(&[], &[])).unwrap().is_empty());
+	}
+
+	#[test]
+	fn rejects_bad_headers() {
+		let data = build(&["wall"], &["models/"]);
+		assert_eq!(material_names(b"IDSQ").unwrap_err().kind(), IoErrorKind::InvalidData);
+		assert_eq!(material_names(&data[..HEADER_SIZE - 1]).unwrap_err().kind(), IoErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn rejects_tables_out_of_bounds() {
+		let data = build(&["wall"], &["models/"]);
+		for (index, value) in [(0, 0x7fffffff), (1, 0xffffffff), (2, 0x7fffffff), (3, 0xffffffff), (0, 100)] {
+			let mut data = data.clone();
+			set_header(&mut data, index, value);
+			let e = material_names(&data).unwrap_err();
+			assert_eq!(e.kind(), IoErrorKind::InvalidData, "header field {index} set to {value:#x}");
+		}
+	}
+
+	#[test]
+	fn truncated_names_are_empty() {
+		let mut data = build(&["wall"], &["models/"]);
+		data[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&0xffffffffu32.to_le_bytes());
+		assert_eq!(material_names(&data).unwrap(), ["models/"]);
+	}
+}
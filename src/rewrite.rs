@@ -0,0 +1,274 @@
+use std::{
+	borrow::Cow,
+	io::{
+		BufRead,
+		Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
+	},
+	mem::take,
+	ops::Range,
+	str::from_utf8,
+};
+
+use slicepat::{
+	Piece, Pieces, Syntax,
+};
+
+use crate::{
+	CaseInsensitivePathMatch, Pattern,
+	normalize,
+};
+
+/// Rules that rewrite the paths of files in the source directory into the paths that they're served from.
+#[derive(Default, Debug)]
+pub struct RewriteRules {
+	rules: Vec<Rule>,
+	/// Whether rewritten paths are lowercased.
+	lowercase: bool,
+}
+
+#[derive(Debug)]
+struct Rule {
+	pattern: Pattern,
+	/// Whether the pattern ends with `**`, which is parsed into a globstar and a wildcard for the last segment, but
+	/// is captured as one.
+	ends_with_globstar: bool,
+	template: Vec<TemplatePart>,
+}
+
+/// Part of the path that a [`Rule`] rewrites a matching path into.
+#[derive(Debug, PartialEq, Eq)]
+enum TemplatePart {
+	Literal(Vec<u8>),
+	/// Span matched by the wildcard of the pattern at this index, counting each `**` as one wildcard.
+	Capture(usize),
+}
+
+impl RewriteRules {
+	/// Read rules from `r`, one per line.
+	///
+	/// Each rule is written as `<pattern> -> <template>`, and the line `lowercase` makes all paths lowercase. Lines
+	/// beginning with `#` are comments.
+	pub fn read_from<R: BufRead>(r: R) -> IoResult<Self> {
+		let mut rules = Self::default();
+		for (index, line) in r.lines().enumerate() {
+			let line = line?;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') { continue }
+			if line == "lowercase" {
+				rules.lowercase = true;
+				continue
+			}
+
+			let rule = parse_rule(line).map_err(move |e| IoError::new(
+				IoErrorKind::InvalidData, format!("line {}: {e}", index + 1),
+			))?;
+			rules.rules.push(rule);
+		}
+		Ok(rules)
+	}
+
+	/// Rewrite `path` with each rule in order, each applying to the path rewritten by the previous ones.
+	///
+	/// Paths are normalized before they're matched, like for ignore patterns, so a path that a rule rewrites is also
+	/// normalized.
+	pub fn rewrite<'a>(&self, path: &'a str) -> Cow<'a, str> {
+		let mut path = Cow::Borrowed(path);
+		for Rule { pattern, ends_with_globstar, template } in self.rules.iter() {
+			let normalized = normalize(path.as_bytes());
+			let Some(mut captures) = pattern.captures(CaseInsensitivePathMatch, &normalized) else { continue };
+			if *ends_with_globstar {
+				merge_last_captures(&mut captures);
+			}
+			let mut rewritten = Vec::with_capacity(normalized.len());
+			for part in template {
+				let part = match part {
+					TemplatePart::Literal(literal) => literal,
+					TemplatePart::Capture(index) => &normalized[captures[*index].clone()],
+				};
+				rewritten.extend_from_slice(part);
+			}
+			path = Cow::Owned(String::from_utf8_lossy(&rewritten).into_owned());
+		}
+		if self.lowercase {
+			path = Cow::Owned(path.to_lowercase());
+		}
+		path
+	}
+}
+
+fn parse_rule(line: &str) -> Result<Rule, String> {
+	let Some((pattern, template)) = split_arrow(line) else {
+		return Err("expected `<pattern> -> <template>`".into())
+	};
+	let pattern_str = normalize(pattern.trim().as_bytes());
+	let pattern = Pattern::parse_with(&pattern_str, &Syntax::<u8>::PATH_GLOB);
+	let ends_with_globstar = ends_with_globstar(&pattern_str, &pattern);
+	let template = parse_template(&normalize(template.trim().as_bytes()))?;
+
+	let capture_count = pattern.capture_count() - usize::from(ends_with_globstar);
+	for part in template.iter() {
+		if let &TemplatePart::Capture(index) = part {
+			if index >= capture_count {
+				return Err(format!("template refers to wildcard {}, but pattern has {capture_count}", index + 1))
+			}
+		}
+	}
+	Ok(Rule { pattern, ends_with_globstar, template })
+}
+
+/// Split `line` around its first `->` that isn't escaped by a `\\` before it, as a pattern can match a literal
+/// `->` by escaping it.
+fn split_arrow(line: &str) -> Option<(&str, &str)> {
+	let bytes = line.as_bytes();
+	let mut i = 0;
+	while i < bytes.len() {
+		match &bytes[i..] {
+			[b'\\', ..] => i += 2,
+			[b'-', b'>', ..] => return Some((&line[..i], &line[i + 2..])),
+			_ => i += 1,
+		}
+	}
+	None
+}
+
+/// Returns `true` if `pattern`, parsed from `source`, ends with a `**` matching any number of segments, which is
+/// parsed into a [`Piece::Globstar`] followed by a [`Piece::Wildcard`] for the last segment.
+///
+/// `a/**/*` is parsed into the same pieces, but has two wildcards in its source.
+fn ends_with_globstar(source: &[u8], pattern: &Pattern) -> bool {
+	let last_pieces = pattern.pieces.pieces().fold((None, None), move |(_, last), piece| (last, Some(piece)));
+	source.ends_with(b"**") && matches!(last_pieces, (Some(Piece::Globstar), Some(Piece::Wildcard)))
+}
+
+/// Join the spans of the last two wildcards, the globstar and the wildcard that a trailing `**` is parsed into.
+fn merge_last_captures(captures: &mut Vec<Range<usize>>) {
+	if let Some(last) = captures.pop() {
+		if let Some(globstar) = captures.last_mut() {
+			globstar.end = last.end;
+		}
+	}
+}
+
+/// Parse a template, in which each wildcard refers to the wildcard of the pattern at the same position, and `$N`
+/// refers to the `N`th wildcard of the pattern, starting from 1. In both, each `**` counts as one wildcard.
+fn parse_template(template: &[u8]) -> Result<Vec<TemplatePart>, String> {
+	let pattern = Pattern::parse_with(template, &Syntax::<u8>::PATH_GLOB);
+	let ends_with_globstar = ends_with_globstar(template, &pattern);
+	let mut parts = Vec::new();
+	let mut next_capture = 0;
+	for piece in pattern.pieces.pieces() {
+		match piece {
+			Piece::Literal(literal) => push_literal(literal, &mut parts)?,
+			_ => {
+				parts.push(TemplatePart::Capture(next_capture));
+				next_capture += 1;
+			}
+		}
+	}
+	if ends_with_globstar {
+		// The globstar's capture already spans the last segment.
+		parts.pop();
+	}
+	Ok(parts)
+}
+
+/// Push the parts of a literal in a template, splitting out `$N` references and unescaping `$$`.
+fn push_literal(mut literal: &[u8], parts: &mut Vec<TemplatePart>) -> Result<(), String> {
+	let mut text = Vec::new();
+	while let Some(dollar) = literal.iter().position(move |&b| b == b'$') {
+		text.extend_from_slice(&literal[..dollar]);
+		let after = &literal[dollar + 1..];
+		let digits = after.iter().take_while(move |b| b.is_ascii_digit()).count();
+		if after.first() == Some(&b'$') {
+			text.push(b'$');
+			literal = &after[1..];
+			continue
+		}
+		let index = from_utf8(&after[..digits]).ok()
+			.and_then(move |digits| digits.parse::<usize>().ok())
+			.and_then(move |number| number.checked_sub(1))
+			.ok_or("expected wildcard number after `$`")?;
+		if !text.is_empty() {
+			parts.push(TemplatePart::Literal(take(&mut text)));
+		}
+		parts.push(TemplatePart::Capture(index));
+		literal = &after[digits..];
+	}
+	text.extend_from_slice(literal);
+	if !text.is_empty() {
+		parts.push(TemplatePart::Literal(text));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn read(text: &str) -> RewriteRules {
+		RewriteRules::read_from(text.as_bytes()).unwrap()
+	}
+
+	#[test]
+	fn strips_prefixes() {
+		let rules = read("custom/*/** -> $2");
+		assert_eq!(rules.rewrite("custom/addon/materials/a/b.vmt"), "materials/a/b.vmt");
+		assert_eq!(rules.rewrite("custom/addon/maps\\a.bsp"), "maps\\a.bsp");
+		assert_eq!(rules.rewrite("maps/a.bsp"), "maps/a.bsp");
+
+		// Wildcards in the template take the captures in order, with `**` as one capture.
+		let rules = read("custom/*/** -> **");
+		assert_eq!(rules.rewrite("custom/addon/materials/a/b.vmt"), "addon");
+		let rules = read("custom/*/** -> */**");
+		assert_eq!(rules.rewrite("custom/addon/materials/a/b.vmt"), "addon/materials/a/b.vmt");
+
+		// A `**` before the last segment doesn't take it.
+		let rules = read("custom/**/* -> $2");
+		assert_eq!(rules.rewrite("custom/a/b/c.vmt"), "c.vmt");
+	}
+
+	#[test]
+	fn applies_rules_in_order() {
+		let rules = read("# comment\n\nsound/*.wav -> sound/old/$1.wav\nsound/old/** -> legacy/$1\n");
+		assert_eq!(rules.rewrite("sound/a.wav"), "legacy/a.wav");
+		assert_eq!(rules.rewrite("sound/a/b.wav"), "sound/a/b.wav");
+		assert_eq!(read("a/* -> $$$1").rewrite("a/b"), "$b");
+		// Only the first unescaped `->` separates the pattern from the template.
+		assert_eq!(read("a\\->b/* -> $1").rewrite("a->b/c"), "c");
+		assert_eq!(read("a/\\\\-> b").rewrite("a/\\"), "b");
+		assert_eq!(read("a/* -> b->$1").rewrite("a/c"), "b->c");
+	}
+
+	#[test]
+	fn lowercases() {
+		let rules = read("lowercase\ncustom/*/** -> $2");
+		assert_eq!(rules.rewrite("Custom/Addon/Materials/Ä.VMT"), "materials/ä.vmt");
+		assert_eq!(rules.rewrite("Maps/A.bsp"), "maps/a.bsp");
+	}
+
+	#[cfg(feature = "unicode")]
+	#[test]
+	fn captures_normalized_paths() {
+		let rules = read("sounds/caf\u{e9}/* -> $1");
+		assert_eq!(rules.rewrite("sounds/cafe\u{301}/a.wav"), "a.wav");
+		let rules = read("sounds/*/a.wav -> $1");
+		assert_eq!(rules.rewrite("sounds/cafe\u{301}/a.wav"), "caf\u{e9}");
+	}
+
+	#[cfg(feature = "unicode")]
+	#[test]
+	fn captures_whole_characters() {
+		assert_eq!(read("maps/caf?.vmt -> x/caf?.vmt").rewrite("maps/café.vmt"), "x/café.vmt");
+		assert_eq!(read("[а-я]* -> $1$2").rewrite("привет"), "привет");
+	}
+
+	#[test]
+	fn rejects_bad_rules() {
+		let error = move |text: &str| RewriteRules::read_from(text.as_bytes()).unwrap_err().to_string();
+		assert_eq!(error("\nmaps/*.bsp"), "line 2: expected `<pattern> -> <template>`");
+		assert_eq!(error("maps\\->*.bsp"), "line 1: expected `<pattern> -> <template>`");
+		assert_eq!(error("maps/** -> $2"), "line 1: template refers to wildcard 2, but pattern has 1");
+		assert_eq!(error("maps/* -> */*"), "line 1: template refers to wildcard 2, but pattern has 1");
+		assert_eq!(error("maps/* -> $x"), "line 1: expected wildcard number after `$`");
+	}
+}
@@ -2,8 +2,9 @@ use rustc_hash::{
 	FxHashMap, FxBuildHasher
 };
 use std::{
+	fs::File,
 	io::{
-		SeekFrom,
+		Cursor, SeekFrom,
 		Write, Read, Seek,
 		BufReader, BufRead,
 		Error as IoError, ErrorKind as IoErrorKind,
@@ -29,31 +30,32 @@ impl<F> State<F> {
 		}
 	}
 
-	fn format_line(path: &Path, time: u64) -> Vec<u8> {
+	fn format_line(path: &Path, time: u128, width: usize) -> Vec<u8> {
 		let mut line = Vec::new();
-		let _ = write!(line, "{time:08x},");
+		let _ = write!(line, "{time:0width$x},");
 		line.extend_from_slice(path.as_os_str().as_encoded_bytes());
 		line
 	}
 
-	fn parse_line(line: &str) -> Result<(PathBuf, u64), IoError> {
+	fn parse_line(line: &str) -> Result<(PathBuf, u128, usize), IoError> {
 		let (secs, path) = line.split_once(',')
 			.ok_or_else(move || IoError::new(
 				IoErrorKind::InvalidData, "expected time and path"
 			))?;
-		let secs = u64::from_str_radix(secs, 16)
+		let time = u128::from_str_radix(secs, 16)
 			.map_err(move |e| IoError::new(IoErrorKind::InvalidData, e))?;
-		Ok((path.trim_end().into(), secs))
+		Ok((path.trim_end().into(), time, secs.len()))
 	}
 }
 
-impl<F: Seek + Write + Read> State<F> {
+impl<F: Seek + Write + Read + SetLen> State<F> {
 	/// Read all consequent entries in the source after the cursor.
 	pub fn read_all_from_cur(&mut self) -> Result<(), IoError> {
 		let mut buf_reader = BufReader::new(&mut self.source);
 		let mut line = String::new();
 		let mut offset = 0;
 		loop {
+			line.clear();
 			let length = buf_reader.read_line(&mut line)? as u64;
 			if length == 0 {
 				break Ok(())
@@ -66,12 +68,12 @@ impl<F: Seek + Write + Read> State<F> {
 				continue
 			}
 
-			let (path, time) = Self::parse_line(line_str)?;
+			let (path, time, width) = Self::parse_line(line_str)?;
 			self.data.insert(path, StateValue {
-				offset: set_offset, 
+				offset: set_offset + (line.len() - line.trim_start().len()) as u64,
 				time,
+				width,
 			});
-			line.clear();
 		}
 	}
 
@@ -83,39 +85,178 @@ impl<F: Seek + Write + Read> State<F> {
 
 	/// Get the last modified time, expressed in seconds after the Unix epoch,
 	/// associated with `path`.
-	pub fn time_of(&self, path: &Path) -> Option<u64> {
+	pub fn time_of(&self, path: &Path) -> Option<u128> {
 		self.data.get(path).map(move |v| v.time)
 	}
 
 	/// Set the last modified time, expressed in seconds after the Unix epoch,
 	/// associated with `path`, to `time`.
+	///
+	/// Other values that change along with the contents of a file, such as checksums, may be stored in place of the
+	/// time.
 	pub fn set_time_of(
-		&mut self, path: &Path, time: u64,
+		&mut self, path: &Path, time: u128,
 	) -> Result<(), IoError> {
 		if let Some(value) = self.data.get_mut(path) {
-			self.source.seek(SeekFrom::Start(value.offset))?;
+			let line = Self::format_line(path, time, value.width);
+			let old_len = value.width + 1 + path.as_os_str().as_encoded_bytes().len();
+			value.time = time;
+			if line.len() == old_len {
+				self.source.seek(SeekFrom::Start(value.offset))?;
+				self.source.write_all(&line)?;
+				return Ok(())
+			}
 
-			self.source.write_all(&Self::format_line(path, time))?;
+			// The new time doesn't fit in place, so the file is rewritten, rather than leaving a blank line behind.
+			value.width = line.len() - 1 - path.as_os_str().as_encoded_bytes().len();
+			return self.compact()
+		}
 
-			value.time = time;
-			Ok(())
+		let offset = self.source.seek(SeekFrom::End(0))?;
+		let offset = if offset != 0 {
+			self.source.write_all(b"\n")?;
+			offset + 1
 		} else {
-			let offset = self.source.seek(SeekFrom::End(0))?;
-			if offset != 0 {
-				self.source.write_all(b"\n")?;
-			}
-			self.source.write_all(&Self::format_line(path, time))?;
+			offset
+		};
+		let line = Self::format_line(path, time, TIME_WIDTH);
+		self.source.write_all(&line)?;
 
-			self.data.insert(path.to_path_buf(), StateValue {
-				offset, time,
-			});
-			Ok(())
+		self.data.insert(path.to_path_buf(), StateValue {
+			offset,
+			time,
+			width: line.len() - 1 - path.as_os_str().as_encoded_bytes().len(),
+		});
+		Ok(())
+	}
+
+	/// Rewrite the source with only the entries, in the order that they're in, dropping blank lines.
+	fn compact(&mut self) -> Result<(), IoError> {
+		let mut entries: Vec<_> = self.data.iter_mut().collect();
+		entries.sort_unstable_by_key(move |(_, value)| value.offset);
+		let mut contents = Vec::new();
+		for (path, value) in entries {
+			if !contents.is_empty() {
+				contents.push(b'\n');
+			}
+			value.offset = contents.len() as u64;
+			contents.extend_from_slice(&Self::format_line(path, value.time, value.width));
 		}
+		self.source.seek(SeekFrom::Start(0))?;
+		self.source.write_all(&contents)?;
+		self.source.set_len(contents.len() as u64)
+	}
+}
+
+/// Source of a [`State`] that can be truncated.
+pub trait SetLen {
+	/// Truncate or extend the source to `len` bytes.
+	fn set_len(&mut self, len: u64) -> Result<(), IoError>;
+}
+
+impl SetLen for File {
+	fn set_len(&mut self, len: u64) -> Result<(), IoError> {
+		File::set_len(self, len)
 	}
 }
 
+impl SetLen for Cursor<Vec<u8>> {
+	fn set_len(&mut self, len: u64) -> Result<(), IoError> {
+		let len = usize::try_from(len).map_err(move |e| IoError::new(IoErrorKind::InvalidInput, e))?;
+		self.get_mut().resize(len, 0);
+		Ok(())
+	}
+}
+
+/// Minimum number of hexadecimal digits that times are written with.
+const TIME_WIDTH: usize = 8;
+
 #[derive(Debug)]
 struct StateValue {
 	pub offset: u64,
-	pub time: u64,
+	pub time: u128,
+	/// Number of digits that the time is written with.
+	pub width: usize,
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::*;
+
+	fn state_from(contents: &str) -> State<Cursor<Vec<u8>>> {
+		let mut state = State::empty(Cursor::new(contents.as_bytes().to_vec()));
+		state.read_all().unwrap();
+		state
+	}
+
+	fn contents(state: State<Cursor<Vec<u8>>>) -> String {
+		String::from_utf8(state.source.into_inner()).unwrap()
+	}
+
+	#[test]
+	fn same_width_rewrites_in_place() {
+		let mut state = state_from("0000000a,maps/a.bsp\n0000000b,maps/b.bsp\n");
+		state.set_time_of(Path::new("maps/a.bsp"), 0xc).unwrap();
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xc));
+		let contents = contents(state);
+		assert_eq!(contents, "0000000c,maps/a.bsp\n0000000b,maps/b.bsp\n");
+
+		let state = state_from(&contents);
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xc));
+		assert_eq!(state.time_of(Path::new("maps/b.bsp")), Some(0xb));
+	}
+
+	#[test]
+	fn wider_value_rewrites_the_file() {
+		let mut state = state_from("0000000a,maps/a.bsp\n\n0000000b,maps/b.bsp\n");
+		state.set_time_of(Path::new("maps/a.bsp"), 0x1_0000_0000).unwrap();
+		// Rewriting the line must replace exactly what was written, whatever its width.
+		state.set_time_of(Path::new("maps/a.bsp"), 0x2_0000_0000).unwrap();
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0x2_0000_0000));
+		// Narrower values are padded to fit in place.
+		state.set_time_of(Path::new("maps/a.bsp"), 0xf).unwrap();
+		state.set_time_of(Path::new("maps/b.bsp"), 0x3_0000_0000).unwrap();
+		let contents = contents(state);
+		assert_eq!(contents, "00000000f,maps/a.bsp\n300000000,maps/b.bsp");
+
+		let state = state_from(&contents);
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xf));
+		assert_eq!(state.time_of(Path::new("maps/b.bsp")), Some(0x3_0000_0000));
+	}
+
+	#[test]
+	fn blanked_line_is_skipped_on_reload() {
+		// Lines were blanked out by earlier versions when their times didn't fit in place.
+		let mut state = state_from("0000000a,maps/a.bsp\n                   \n\n100000000,maps/b.bsp");
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xa));
+		assert_eq!(state.time_of(Path::new("maps/b.bsp")), Some(0x1_0000_0000));
+		state.set_time_of(Path::new("maps/a.bsp"), 0xd).unwrap();
+		state.set_time_of(Path::new("maps/b.bsp"), 0x1_0000_0001).unwrap();
+
+		let state = state_from(&contents(state));
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xd));
+		assert_eq!(state.time_of(Path::new("maps/b.bsp")), Some(0x1_0000_0001));
+	}
+
+	#[test]
+	fn missing_trailing_newline() {
+		let mut state = state_from("0000000a,maps/a.bsp");
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xa));
+		state.set_time_of(Path::new("maps/b.bsp"), 0xb).unwrap();
+		state.set_time_of(Path::new("maps/a.bsp"), 0xe).unwrap();
+		let contents = contents(state);
+		assert_eq!(contents, "0000000e,maps/a.bsp\n0000000b,maps/b.bsp");
+
+		let state = state_from(&contents);
+		assert_eq!(state.time_of(Path::new("maps/a.bsp")), Some(0xe));
+		assert_eq!(state.time_of(Path::new("maps/b.bsp")), Some(0xb));
+	}
+
+	#[test]
+	fn invalid_line_is_rejected() {
+		let mut state = State::empty(Cursor::new(b"maps/a.bsp\n".to_vec()));
+		assert_eq!(state.read_all().unwrap_err().kind(), IoErrorKind::InvalidData);
+	}
 }
@@ -0,0 +1,307 @@
+use std::{
+	fs::File,
+	io::{
+		Cursor, SeekFrom,
+		Read, Seek,
+		BufReader, BufRead,
+		Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
+	},
+	path::{
+		Path, PathBuf
+	},
+};
+
+const SIGNATURE: u32 = 0x55aa1234;
+const HEADER_V1_SIZE: u64 = 12;
+const HEADER_V2_SIZE: u64 = 28;
+const ENTRY_TERMINATOR: u16 = 0xffff;
+
+/// Archive index of entries whose data is stored in the directory file itself.
+pub const DIRECTORY_ARCHIVE: u16 = 0x7fff;
+
+/// Valve Pak (VPK) directory, listing entries and where their data lives.
+#[derive(Debug)]
+pub struct Vpk {
+	path: PathBuf,
+	data_offset: u64,
+	pub entries: Vec<VpkEntry>,
+}
+
+/// Single file stored in a [`Vpk`].
+#[derive(Debug, Clone)]
+pub struct VpkEntry {
+	/// Path of the entry, with `/` as the separator.
+	pub path: String,
+	pub crc: u32,
+	/// Data stored in the directory tree, preceding the archived data.
+	pub preload: Vec<u8>,
+	pub archive_index: u16,
+	pub offset: u32,
+	pub length: u32,
+}
+
+impl VpkEntry {
+	/// Value identifying the contents of this entry, for change detection.
+	pub fn fingerprint(&self) -> u128 {
+		(u128::from(self.crc) << 80)
+			| (u128::from(self.archive_index) << 64)
+			| (u128::from(self.length) << 32)
+			| u128::from(self.offset)
+	}
+}
+
+impl Vpk {
+	/// Read the directory tree of the VPK at `path`.
+	///
+	/// `path` should be either a single-file VPK, or the `_dir.vpk` file of a multi-archive VPK.
+	pub fn open(path: &Path) -> IoResult<Self> {
+		Self::read_from(BufReader::new(File::open(path)?), path)
+	}
+
+	/// Read the directory tree of the VPK at `path` from `r`.
+	fn read_from<R: BufRead + Seek>(mut r: R, path: &Path) -> IoResult<Self> {
+		if read_u32(&mut r)? != SIGNATURE {
+			return Err(IoError::new(IoErrorKind::InvalidData, "not a VPK file"))
+		}
+		let header_size = match read_u32(&mut r)? {
+			1 => HEADER_V1_SIZE,
+			2 => HEADER_V2_SIZE,
+			version => return Err(IoError::new(
+				IoErrorKind::InvalidData, format!("unsupported VPK version {version}")
+			)),
+		};
+		let tree_size = u64::from(read_u32(&mut r)?);
+		r.seek(SeekFrom::Start(header_size))?;
+
+		let mut entries = Vec::new();
+		loop {
+			let extension = read_cstr(&mut r)?;
+			if extension.is_empty() { break }
+			loop {
+				let directory = read_cstr(&mut r)?;
+				if directory.is_empty() { break }
+				loop {
+					let name = read_cstr(&mut r)?;
+					if name.is_empty() { break }
+
+					let mut path = String::new();
+					if directory != " " {
+						path.push_str(&directory);
+						path.push('/');
+					}
+					path.push_str(&name);
+					if extension != " " {
+						path.push('.');
+						path.push_str(&extension);
+					}
+
+					entries.push(read_entry(&mut r, path)?);
+				}
+			}
+		}
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			data_offset: header_size + tree_size,
+			entries,
+		})
+	}
+
+	/// Get the path of the file containing the data for `entry`, and the offset of that data in the file.
+	pub fn data_location(&self, entry: &VpkEntry) -> IoResult<(PathBuf, u64)> {
+		if entry.archive_index == DIRECTORY_ARCHIVE {
+			return Ok((self.path.clone(), self.data_offset + u64::from(entry.offset)))
+		}
+
+		let stem = self.path.file_stem()
+			.and_then(move |stem| stem.to_str())
+			.and_then(move |stem| stem.strip_suffix("_dir"))
+			.ok_or_else(move || IoError::new(
+				IoErrorKind::InvalidData,
+				format!("entry {:?} is in archive {}, but VPK has no archives", entry.path, entry.archive_index),
+			))?;
+		let archive_path = self.path.with_file_name(format!("{stem}_{:03}.vpk", entry.archive_index));
+		Ok((archive_path, u64::from(entry.offset)))
+	}
+}
+
+/// Open a reader over preloaded data, followed by `length` bytes at `offset` in the file at `path`.
+pub fn open_data(path: &Path, offset: u64, length: u32, preload: Vec<u8>) -> IoResult<impl Read> {
+	let mut file = File::open(path)?;
+	file.seek(SeekFrom::Start(offset))?;
+	Ok(Cursor::new(preload).chain(file.take(u64::from(length))))
+}
+
+/// Returns `true` if `path` names a VPK that lists entries, as opposed to a numbered archive of a multi-archive
+/// VPK.
+pub fn is_directory_file(path: &Path) -> bool {
+	if !path.extension().is_some_and(move |ext| ext.eq_ignore_ascii_case("vpk")) {
+		return false
+	}
+	let Some(stem) = path.file_stem().and_then(move |stem| stem.to_str()) else {
+		return true
+	};
+	match stem.rsplit_once('_') {
+		Some((_, index)) => !(index.len() == 3 && index.bytes().all(move |b| b.is_ascii_digit())),
+		None => true,
+	}
+}
+
+fn read_entry<R: Read>(r: &mut R, path: String) -> IoResult<VpkEntry> {
+	let crc = read_u32(r)?;
+	let preload_len = read_u16(r)?;
+	let archive_index = read_u16(r)?;
+	let offset = read_u32(r)?;
+	let length = read_u32(r)?;
+	if read_u16(r)? != ENTRY_TERMINATOR {
+		return Err(IoError::new(IoErrorKind::InvalidData, format!("bad terminator for VPK entry {path:?}")))
+	}
+	let mut preload = vec![0; usize::from(preload_len)];
+	r.read_exact(&mut preload)?;
+	Ok(VpkEntry {
+		path, crc, preload, archive_index, offset, length,
+	})
+}
+
+fn read_cstr<R: BufRead>(r: &mut R) -> IoResult<String> {
+	let mut buf = Vec::new();
+	r.read_until(0, &mut buf)?;
+	if buf.pop() != Some(0) {
+		return Err(IoError::new(IoErrorKind::UnexpectedEof, "unterminated string in VPK tree"))
+	}
+	String::from_utf8(buf).map_err(move |e| IoError::new(IoErrorKind::InvalidData, e))
+}
+
+fn read_u16<R: Read>(r: &mut R) -> IoResult<u16> {
+	let mut buf = [0; 2];
+	r.read_exact(&mut buf)?;
+	Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> IoResult<u32> {
+	let mut buf = [0; 4];
+	r.read_exact(&mut buf)?;
+	Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Entry as `(extension, directory, name, preload, archive_index, length)`.
+	type TestEntry<'a> = (&'a str, &'a str, &'a str, &'a [u8], u16, u32);
+
+	/// Build a VPK of `version` with `entries`, each in its own extension and directory list.
+	fn build(version: u32, entries: &[TestEntry]) -> Vec<u8> {
+		let mut tree = Vec::new();
+		for &(extension, directory, name, preload, archive_index, length) in entries {
+			for s in [extension, directory, name] {
+				tree.extend_from_slice(s.as_bytes());
+				tree.push(0);
+			}
+			tree.extend_from_slice(&0x1234u32.to_le_bytes());
+			tree.extend_from_slice(&(preload.len() as u16).to_le_bytes());
+			tree.extend_from_slice(&archive_index.to_le_bytes());
+			tree.extend_from_slice(&0u32.to_le_bytes());
+			tree.extend_from_slice(&length.to_le_bytes());
+			tree.extend_from_slice(&ENTRY_TERMINATOR.to_le_bytes());
+			tree.extend_from_slice(preload);
+			// Close the name and directory lists.
+			tree.extend_from_slice(&[0, 0]);
+		}
+		tree.push(0);
+
+		let mut data = Vec::new();
+		data.extend_from_slice(&SIGNATURE.to_le_bytes());
+		data.extend_from_slice(&version.to_le_bytes());
+		data.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+		if version == 2 {
+			data.extend_from_slice(&[0; (HEADER_V2_SIZE - HEADER_V1_SIZE) as usize]);
+		}
+		data.extend_from_slice(&tree);
+		data
+	}
+
+	fn read(data: Vec<u8>, path: &str) -> IoResult<Vpk> {
+		Vpk::read_from(Cursor::new(data), Path::new(path))
+	}
+
+	#[test]
+	fn reads_v1_and_v2_headers() {
+		for version in [1, 2] {
+			let data = build(version, &[
+				("vmt", "materials/brick", "wall", b"", 0, 10),
+				("txt", " ", "readme", b"", 1, 20),
+			]);
+			let tree_end = data.len() as u64;
+			let vpk = read(data, "pak01_dir.vpk").unwrap();
+			let paths: Vec<_> = vpk.entries.iter().map(move |entry| entry.path.as_str()).collect();
+			assert_eq!(paths, ["materials/brick/wall.vmt", "readme.txt"]);
+			assert_eq!(vpk.data_offset, tree_end);
+			assert_eq!(vpk.entries[1].length, 20);
+			assert_eq!(vpk.data_location(&vpk.entries[1]).unwrap(), (PathBuf::from("pak01_001.vpk"), 0));
+		}
+	}
+
+	#[test]
+	fn rejects_bad_headers() {
+		let mut data = build(1, &[]);
+		data[0] = 0;
+		assert_eq!(read(data, "a.vpk").unwrap_err().kind(), IoErrorKind::InvalidData);
+		assert_eq!(read(build(3, &[]), "a.vpk").unwrap_err().kind(), IoErrorKind::InvalidData);
+		assert_eq!(read(build(1, &[])[..6].to_vec(), "a.vpk").unwrap_err().kind(), IoErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn rejects_truncated_trees() {
+		let data = build(2, &[("vmt", "materials", "wall", b"preload", DIRECTORY_ARCHIVE, 0)]);
+		for len in HEADER_V2_SIZE as usize..data.len() - 1 {
+			let e = read(data[..len].to_vec(), "a.vpk").unwrap_err();
+			assert_eq!(e.kind(), IoErrorKind::UnexpectedEof, "truncated to {len} bytes");
+		}
+
+		let mut data = build(1, &[("vmt", "materials", "wall", b"", 0, 0)]);
+		let terminator = data.len() - 5;
+		data[terminator] = 0;
+		assert_eq!(read(data, "a.vpk").unwrap_err().kind(), IoErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn reads_preload_only_entries() {
+		let data = build(1, &[("txt", "cfg", "settings", b"preloaded", DIRECTORY_ARCHIVE, 0)]);
+		let tree_end = data.len() as u64;
+		let vpk = read(data, "pak01_dir.vpk").unwrap();
+		let entry = &vpk.entries[0];
+		assert_eq!(entry.preload, b"preloaded");
+		assert_eq!(entry.length, 0);
+		assert_eq!(vpk.data_location(entry).unwrap(), (PathBuf::from("pak01_dir.vpk"), tree_end));
+	}
+
+	#[test]
+	fn entries_in_other_archives_need_a_directory_file() {
+		let vpk = read(build(1, &[("vmt", "materials", "wall", b"", 2, 10)]), "single.vpk").unwrap();
+		assert_eq!(vpk.data_location(&vpk.entries[0]).unwrap_err().kind(), IoErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn fingerprint_covers_location_and_length() {
+		let entry = VpkEntry {
+			path: "a.txt".into(),
+			crc: 1,
+			preload: Vec::new(),
+			archive_index: 0,
+			offset: 0,
+			length: 0,
+		};
+		let fingerprints = [
+			entry.fingerprint(),
+			VpkEntry { crc: 2, ..entry.clone() }.fingerprint(),
+			VpkEntry { archive_index: 1, ..entry.clone() }.fingerprint(),
+			VpkEntry { offset: 1, ..entry.clone() }.fingerprint(),
+			VpkEntry { length: 1, ..entry.clone() }.fingerprint(),
+		];
+		for (i, a) in fingerprints.iter().enumerate() {
+			assert!(fingerprints[i + 1..].iter().all(move |b| a != b));
+		}
+	}
+}
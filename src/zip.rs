@@ -0,0 +1,131 @@
+use std::io::{
+	Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
+};
+
+use crate::bytes::{
+	le_u16, le_u32,
+};
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: &[u8; 4] = b"PK\x05\x06";
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: &[u8; 4] = b"PK\x01\x02";
+const CENTRAL_DIRECTORY_HEADER_SIZE: usize = 46;
+const MAX_COMMENT_LEN: usize = u16::MAX as usize;
+
+/// File listed in the central directory of a ZIP archive.
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+	pub name: String,
+	pub crc: u32,
+	/// Size of the file when uncompressed.
+	pub size: u32,
+}
+
+/// List the files in the ZIP archive `data`.
+pub fn entries(data: &[u8]) -> IoResult<Vec<ZipEntry>> {
+	let invalid = move |message: &str| IoError::new(IoErrorKind::InvalidData, message.to_string());
+
+	let search_start = data.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE + MAX_COMMENT_LEN);
+	let end_offset = data.get(search_start..).unwrap_or_default()
+		.windows(END_OF_CENTRAL_DIRECTORY_SIGNATURE.len())
+		.rposition(move |window| window == END_OF_CENTRAL_DIRECTORY_SIGNATURE)
+		.map(move |offset| search_start + offset)
+		.ok_or_else(move || invalid("couldn't find end of ZIP central directory"))?;
+
+	let count = le_u16(data, end_offset + 10).ok_or_else(move || invalid("truncated end of central directory"))?;
+	let mut offset = le_u32(data, end_offset + 16).ok_or_else(move || invalid("truncated end of central directory"))?
+		as usize;
+
+	let mut entries = Vec::with_capacity(usize::from(count));
+	for _ in 0..count {
+		let header = data.get(offset..offset + CENTRAL_DIRECTORY_HEADER_SIZE)
+			.filter(move |header| header.starts_with(CENTRAL_DIRECTORY_HEADER_SIGNATURE))
+			.ok_or_else(move || invalid("bad ZIP central directory header"))?;
+		let field16 = move |at| usize::from(le_u16(header, at).unwrap_or(0));
+		let field32 = move |at| le_u32(header, at).unwrap_or(0);
+
+		let name_len = field16(28);
+		let extra_len = field16(30);
+		let comment_len = field16(32);
+		let name_start = offset + CENTRAL_DIRECTORY_HEADER_SIZE;
+		let name = data.get(name_start..name_start + name_len)
+			.ok_or_else(move || invalid("truncated ZIP file name"))?;
+		let name = String::from_utf8_lossy(name).into_owned();
+
+		if !name.ends_with('/') {
+			entries.push(ZipEntry {
+				name,
+				crc: field32(16),
+				size: field32(24),
+			});
+		}
+		offset = name_start + name_len + extra_len + comment_len;
+	}
+	Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Build an archive whose central directory lists `names`, each with a CRC and size of its index, followed by
+	/// `comment`. File contents aren't needed to list entries, so there are none.
+	fn build(names: &[&str], comment: &[u8]) -> Vec<u8> {
+		let mut data = b"local file data".to_vec();
+		let directory_offset = data.len();
+		for (index, name) in names.iter().enumerate() {
+			let mut header = [0; CENTRAL_DIRECTORY_HEADER_SIZE];
+			header[..4].copy_from_slice(CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+			header[16..20].copy_from_slice(&(index as u32).to_le_bytes());
+			header[24..28].copy_from_slice(&(index as u32).to_le_bytes());
+			header[28..30].copy_from_slice(&(name.len() as u16).to_le_bytes());
+			header[30..32].copy_from_slice(&2u16.to_le_bytes());
+			data.extend_from_slice(&header);
+			data.extend_from_slice(name.as_bytes());
+			data.extend_from_slice(b"xx");
+		}
+		let mut end = [0; END_OF_CENTRAL_DIRECTORY_SIZE];
+		end[..4].copy_from_slice(END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+		end[10..12].copy_from_slice(&(names.len() as u16).to_le_bytes());
+		end[16..20].copy_from_slice(&(directory_offset as u32).to_le_bytes());
+		end[20..22].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+		data.extend_from_slice(&end);
+		data.extend_from_slice(comment);
+		data
+	}
+
+	fn names(entries: &[ZipEntry]) -> Vec<&str> {
+		entries.iter().map(move |entry| entry.name.as_str()).collect()
+	}
+
+	#[test]
+	fn lists_files() {
+		let data = build(&["materials/", "materials/wall.vmt", "sound/a.wav"], b"comment");
+		let files = entries(&data).unwrap();
+		assert_eq!(names(&files), ["materials/wall.vmt", "sound/a.wav"]);
+		assert_eq!((files[1].crc, files[1].size), (2, 2));
+		assert!(entries(&build(&[], b"")).unwrap().is_empty());
+	}
+
+	#[test]
+	fn rejects_bad_archives() {
+		let data = build(&["materials/wall.vmt"], b"");
+		let message = move |data: &[u8]| entries(data).unwrap_err().to_string();
+		assert_eq!(message(b""), "couldn't find end of ZIP central directory");
+		assert_eq!(message(&data[..data.len() - 5]), "truncated end of central directory");
+
+		let mut bad_offset = data.clone();
+		let end = bad_offset.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+		bad_offset[end + 16..end + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert_eq!(message(&bad_offset), "bad ZIP central directory header");
+
+		let mut bad_count = data.clone();
+		bad_count[end + 10..end + 12].copy_from_slice(&2u16.to_le_bytes());
+		assert_eq!(message(&bad_count), "bad ZIP central directory header");
+
+		let mut bad_name = data;
+		let name_len_offset = b"local file data".len() + 28;
+		bad_name[name_len_offset..name_len_offset + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+		assert_eq!(message(&bad_name), "truncated ZIP file name");
+	}
+}